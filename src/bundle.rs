@@ -0,0 +1,239 @@
+use std::{fs::{self, File}, path::{Path, PathBuf}, collections::BTreeSet, time::UNIX_EPOCH};
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use tar::{Archive, Builder};
+use yansi::Paint;
+
+use crate::{config::{Config, VersionsConfig}, services::kf_installer::KfInstaller};
+
+static MANIFEST_FILE: &str = "bundle.toml";
+static UNPACKED_DIR: &str = "bundles";
+pub static BIN_DIR: &str = "bin";
+pub static WHEELS_DIR: &str = "wheels";
+pub static MANIFESTS_DIR: &str = "manifests";
+pub static IMAGES_DIR: &str = "images";
+
+/// Describes what an offline bundle was created with (which is what it installs).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub k3d_image: String,
+    pub kfp_version: String,
+    /// The `kubeflow/manifests` release that KubeFlow is deployed from (`None` when the bundle only has KFP).
+    #[serde(default)]
+    pub kf_manifests_version: Option<String>,
+    /// The picked KubeFlow components that were rendered (empty for every component of the example).
+    #[serde(default)]
+    pub components: Vec<String>,
+    /// The container images saved into the bundle.
+    pub images: Vec<String>,
+    // Tables have to come after the plain values in TOML.
+    pub versions: VersionsConfig,
+}
+
+/// An offline bundle, unpacked into the cache (only once per archive, since bundles run to gigabytes).
+pub struct Bundle {
+    dir: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Bundle {
+    /// Unpacks the bundle at `path`, unless the same archive was unpacked already.
+    pub fn open(path: &str) -> Result<Self> {
+        let unpacked_dir = unpacked_dir()?;
+        let dir = unpacked_dir.join(archive_key(path)?);
+
+        if !dir.is_dir() {
+            unpack(path, &unpacked_dir, &dir)?;
+        }
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let text = fs::read_to_string(&manifest_path).with_context(|| format!("The bundle `{}` has no `{}`.", path, MANIFEST_FILE))?;
+        let manifest = toml::from_str(&text).with_context(|| format!("Unable to parse the `{}` of the bundle `{}`.", MANIFEST_FILE, path))?;
+
+        Ok(Self { dir, manifest })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Pins the config to what the bundle contains (since nothing else can be installed offline).
+    pub fn apply(&self, config: &mut Config) {
+        let manifest = &self.manifest;
+
+        if config.service.kfp_version != manifest.kfp_version || config.service.k3d_image != manifest.k3d_image {
+            println!("{}", Paint::yellow(format!("Using KFP {} and `{}` from the bundle.", manifest.kfp_version, manifest.k3d_image)));
+        }
+
+        config.versions = manifest.versions.clone();
+        config.service.kfp_version = manifest.kfp_version.clone();
+        config.service.k3d_image = manifest.k3d_image.clone();
+
+        if let Some(version) = &manifest.kf_manifests_version {
+            let service = &mut config.service;
+
+            if service.kf_installer != KfInstaller::Manifests || service.kf_manifests_version != *version || service.components != manifest.components {
+                println!("{}", Paint::yellow(format!("Using the KubeFlow manifests {} from the bundle.", version)));
+            }
+
+            service.kf_installer = KfInstaller::Manifests;
+            service.kf_manifests_version = version.clone();
+            service.components = manifest.components.clone();
+        }
+    }
+}
+
+/// Returns where bundles are unpacked.
+#[cfg(not(test))]
+fn unpacked_dir() -> Result<PathBuf> {
+    crate::helpers::cache_dir()
+        .map(|d| d.join(UNPACKED_DIR))
+        .context("Unable to locate the cache directory (neither `XDG_CACHE_HOME` nor `HOME` is set).")
+}
+
+/// Keeps the tests away from the real cache.
+#[cfg(test)]
+fn unpacked_dir() -> Result<PathBuf> {
+    Ok(std::env::temp_dir().join(format!("kfpl-test-{}", std::process::id())).join(UNPACKED_DIR))
+}
+
+/// Names an archive after its path, size, and modification time, so that a changed archive is unpacked again.
+fn archive_key(path: &str) -> Result<String> {
+    let metadata = fs::metadata(path).with_context(|| format!("Unable to open the bundle `{}`.", path))?;
+    let canonical = fs::canonicalize(path).with_context(|| format!("Unable to open the bundle `{}`.", path))?;
+    let modified = metadata.modified().ok().and_then(|m| m.duration_since(UNIX_EPOCH).ok()).unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(canonical.to_string_lossy().as_bytes());
+    hasher.update(metadata.len().to_le_bytes());
+    hasher.update(modified.as_nanos().to_le_bytes());
+
+    Ok(hasher.finalize().iter().take(8).map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Unpacks the archive next to `dir`, and then renames it into place, so that an interrupted unpack is never taken for
+/// a complete one.  Only the latest bundle is kept unpacked (the others are removed first).
+fn unpack(path: &str, unpacked_dir: &Path, dir: &Path) -> Result<()> {
+    if unpacked_dir.is_dir() {
+        for entry in fs::read_dir(unpacked_dir).context("Unable to list the unpacked bundles.")? {
+            let stale = entry.context("Unable to list the unpacked bundles.")?.path();
+            fs::remove_dir_all(&stale).with_context(|| format!("Unable to remove the unpacked bundle `{}`.", stale.display()))?;
+        }
+    }
+
+    let partial = dir.with_extension("partial");
+    fs::create_dir_all(&partial).with_context(|| format!("Unable to create `{}`.", partial.display()))?;
+
+    let file = File::open(path).with_context(|| format!("Unable to open the bundle `{}`.", path))?;
+    Archive::new(file).unpack(&partial).with_context(|| format!("Unable to unpack the bundle `{}`.", path))?;
+
+    fs::rename(&partial, dir).with_context(|| format!("Unable to move the unpacked bundle into `{}`.", dir.display()))
+}
+
+/// Writes the manifest into the staged bundle directory, and packs it into an archive at `path`.
+pub fn create(dir: &Path, manifest: &Manifest, path: &str) -> Result<()> {
+    let text = toml::to_string(manifest).context("Unable to serialize the bundle manifest.")?;
+    fs::write(dir.join(MANIFEST_FILE), text).context("Unable to write the bundle manifest.")?;
+
+    let file = File::create(path).with_context(|| format!("Unable to create the bundle `{}`.", path))?;
+    let mut builder = Builder::new(file);
+
+    builder.append_dir_all(".", dir).with_context(|| format!("Unable to write the bundle `{}`.", path))?;
+    builder.into_inner().with_context(|| format!("Unable to write the bundle `{}`.", path))?;
+
+    Ok(())
+}
+
+/// Returns the path of a file within the bundle directory (e.g., `bin/kubectl`).
+pub fn file(dir: &Path, subdir: &str, name: &str) -> PathBuf {
+    dir.join(subdir).join(name)
+}
+
+/// Returns every container image referenced by the manifests in the bundle directory (in order, without duplicates).
+pub fn manifest_images(dir: &Path) -> Result<Vec<String>> {
+    let manifests_dir = dir.join(MANIFESTS_DIR);
+
+    if !manifests_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&manifests_dir)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+    paths.sort();
+
+    let mut images = BTreeSet::new();
+
+    for path in paths {
+        let text = fs::read_to_string(&path).with_context(|| format!("Unable to read the manifest `{}`.", path.display()))?;
+        images.extend(images_in(&text));
+    }
+
+    Ok(images.into_iter().collect())
+}
+
+/// Finds the `image` values, and the images that controllers launch themselves (keys that end in `Image`, such as
+/// argo's `executorImage`, which sits in the JSON-ish config of a config map).
+fn images_in(yaml: &str) -> Vec<String> {
+    let is_quote = |c| c == '"' || c == '\'';
+
+    yaml.lines()
+        .filter_map(|l| l.trim_start().trim_start_matches("- ").split_once(':'))
+        .filter(|(key, _)| {
+            let key = key.trim_matches(is_quote);
+            key == "image" || key.ends_with("Image")
+        })
+        .map(|(_, value)| value.trim().trim_end_matches(',').trim_matches(is_quote).to_owned())
+        .filter(|i| !i.is_empty() && !i.contains(char::is_whitespace) && !i.starts_with(['{', '|', '>', '$']))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_in_finds_container_images() {
+        let yaml = "spec:\n  containers:\n  - image: gcr.io/ml-pipeline/api-server:1.0.4\n    name: api\n  - name: cache\n    image: \"gcr.io/ml-pipeline/cache-server:1.0.4\"\n";
+
+        assert_eq!(images_in(yaml), vec!["gcr.io/ml-pipeline/api-server:1.0.4", "gcr.io/ml-pipeline/cache-server:1.0.4"]);
+    }
+
+    #[test]
+    fn images_in_finds_the_images_that_controllers_launch() {
+        let yaml = "kind: ConfigMap\nmetadata:\n  name: workflow-controller-configmap\ndata:\n  config: |\n    {\n    executorImage: gcr.io/ml-pipeline/argoexec:v2.7.5-license-compliance,\n    containerRuntimeExecutor: docker,\n    }\n  imagePullPolicy: IfNotPresent\n";
+
+        assert_eq!(images_in(yaml), vec!["gcr.io/ml-pipeline/argoexec:v2.7.5-license-compliance"]);
+    }
+
+    #[test]
+    fn bundles_round_trip() {
+        let staging = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let archive = out.path().join("bundle.tar");
+        let archive = archive.to_str().unwrap();
+
+        fs::create_dir_all(staging.path().join(BIN_DIR)).unwrap();
+        fs::write(file(staging.path(), BIN_DIR, "k9s"), "k9s!").unwrap();
+
+        let manifest = Manifest {
+            k3d_image: "rancher/k3s:v1.20.2-k3s1".to_owned(),
+            kfp_version: "1.0.4".to_owned(),
+            kf_manifests_version: Some("v1.3.0".to_owned()),
+            components: vec!["pipelines".to_owned()],
+            images: vec![],
+            versions: VersionsConfig::default()
+        };
+        create(staging.path(), &manifest, archive).unwrap();
+
+        let bundle = Bundle::open(archive).unwrap();
+
+        assert_eq!(fs::read_to_string(file(bundle.dir(), BIN_DIR, "k9s")).unwrap(), "k9s!");
+        assert_eq!(bundle.manifest.kfp_version, "1.0.4");
+        assert_eq!(bundle.manifest.kf_manifests_version.as_deref(), Some("v1.3.0"));
+        assert_eq!(bundle.manifest.components, vec!["pipelines"]);
+
+        // The same archive is only unpacked once.
+        fs::write(file(bundle.dir(), BIN_DIR, "k9s"), "cached!").unwrap();
+        assert_eq!(fs::read_to_string(file(Bundle::open(archive).unwrap().dir(), BIN_DIR, "k9s")).unwrap(), "cached!");
+    }
+}
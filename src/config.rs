@@ -0,0 +1,337 @@
+use std::{env, fs, path::PathBuf, collections::BTreeMap};
+use anyhow::{Result, Context, anyhow};
+use serde::{Serialize, Deserialize};
+use toml::Value;
+
+use crate::{services::{k3d, kubectl, kfctl, k9s, kustomize, kf_service, platform::Arch, provider::Provider, kf_installer::KfInstaller}, helpers};
+
+static LOCAL_CONFIG: &str = "kfpl.toml";
+static USER_CONFIG: &str = "kfpl/config.toml";
+static PROFILES_KEY: &str = "profiles";
+static DESCRIPTION_KEY: &str = "description";
+
+// Configuration model (every field falls back to the built-in default).
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub service: ServiceConfig,
+    pub ui: UiConfig,
+    pub versions: VersionsConfig,
+    pub checksums: ChecksumsConfig,
+    pub install: InstallConfig,
+    /// The name of the profile applied on top of the base settings, if any.
+    #[serde(skip)]
+    pub profile: Option<String>,
+    /// Every profile defined in the config files, each fully resolved against the base settings.
+    #[serde(skip)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A named bundle of `service` and `ui` settings, defined under `[profiles.<name>]`.
+#[derive(Debug)]
+pub struct Profile {
+    pub description: Option<String>,
+    pub config: Config,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+    pub kfp_only: bool,
+    pub provider: Provider,
+    /// The name of the cluster (for every provider).
+    pub k3d_cluster_name: String,
+    pub k3d_image: String,
+    pub k3d_api_address: String,
+    pub k3d_api_port: u16,
+    /// The k3d pass-through options (the k3d defaults apply when they are not set).
+    pub k3d_servers: Option<u16>,
+    pub k3d_agents: Option<u16>,
+    pub k3d_ports: Vec<String>,
+    pub k3d_volumes: Vec<String>,
+    pub k3s_server_args: Vec<String>,
+    pub k3d_env: Vec<String>,
+    pub k3d_labels: Vec<String>,
+    /// Appended, verbatim, to `k3d cluster create`.
+    pub k3d_extra_args: Vec<String>,
+    /// Maps this host port to the k3d load balancer, and routes it to the UI with an ingress.
+    pub expose_port: Option<u16>,
+    /// Creates (or reuses) a local registry, and connects the k3d cluster to it.
+    pub registry: bool,
+    pub registry_name: String,
+    pub registry_port: u16,
+    /// How long (in seconds) to wait for each rollout (the cluster, and the deployment) to become ready.
+    pub wait_timeout: u64,
+    pub kfp_version: String,
+    /// Deploys KubeFlow from the `kubeflow/manifests` example (with `kustomize`), or from the kfdef (with `kfctl`).
+    pub kf_installer: KfInstaller,
+    pub kf_manifests_version: String,
+    /// The KubeFlow components to deploy (with their dependencies), rather than every one in the manifests example.
+    pub components: Vec<String>,
+    pub kf_yaml: String,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            kfp_only: false,
+            provider: Provider::default(),
+            k3d_cluster_name: "kfp-local".to_owned(),
+            k3d_image: "rancher/k3s:v1.20.2-k3s1".to_owned(),
+            k3d_api_address: "0.0.0.0".to_owned(),
+            k3d_api_port: 6443,
+            k3d_servers: None,
+            k3d_agents: None,
+            k3d_ports: Vec::new(),
+            k3d_volumes: Vec::new(),
+            k3s_server_args: Vec::new(),
+            k3d_env: Vec::new(),
+            k3d_labels: Vec::new(),
+            k3d_extra_args: Vec::new(),
+            expose_port: None,
+            registry: false,
+            registry_name: "kfpl-registry".to_owned(),
+            registry_port: 5000,
+            wait_timeout: 600,
+            kfp_version: "1.0.4".to_owned(),
+            kf_installer: KfInstaller::default(),
+            kf_manifests_version: kf_service::MANIFESTS_VERSION.to_owned(),
+            components: Vec::new(),
+            kf_yaml: "https://raw.githubusercontent.com/kubeflow/manifests/v1.2-branch/kfdef/kfctl_k8s_istio.v1.2.0.yaml".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    pub kfp_only: bool,
+    pub port: u16,
+    pub address: String,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            kfp_only: false,
+            port: 8080,
+            address: "0.0.0.0".to_owned(),
+        }
+    }
+}
+
+/// The versions that `init` installs, and `upgrade` reinstalls to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VersionsConfig {
+    pub k3d: String,
+    pub kubectl: String,
+    pub kfctl: String,
+    pub kustomize: String,
+    pub k9s: String,
+    /// The kfp SDK, which follows the deployed KFP backend (`service.kfp_version`) unless it is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kfp: Option<String>,
+}
+
+impl VersionsConfig {
+    /// Returns the kfp SDK version for a KFP backend.  This assumes that the SDK is released alongside the backend, at the
+    /// same version (e.g., kfp 1.0.4 for KFP 1.0.4), which holds for the KFP 1.x releases; `versions.kfp` pins another
+    /// one for a backend where it does not.
+    pub fn kfp_sdk<'a>(&'a self, kfp_version: &'a str) -> &'a str {
+        self.kfp.as_deref().unwrap_or(kfp_version)
+    }
+}
+
+impl Default for VersionsConfig {
+    fn default() -> Self {
+        Self {
+            k3d: k3d::VERSION.to_owned(),
+            kubectl: kubectl::VERSION.to_owned(),
+            kfctl: kfctl::VERSION.to_owned(),
+            kustomize: kustomize::VERSION.to_owned(),
+            k9s: k9s::VERSION.to_owned(),
+            kfp: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InstallConfig {
+    /// The directory that the tools are installed into (`/usr/local/bin` for root, and otherwise `~/.local/bin`).
+    pub prefix: String,
+}
+
+impl Default for InstallConfig {
+    fn default() -> Self {
+        Self {
+            prefix: helpers::default_prefix(),
+        }
+    }
+}
+
+/// The SHA-256 checksums that downloads are verified against, per tool, version, and architecture (e.g.,
+/// `kubectl."v1.19.2".amd64`), which take precedence over the built-in ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChecksumsConfig {
+    /// Refuses to install any download without a known checksum (rather than warning about it).
+    pub require: bool,
+    pub k3d: ToolChecksums,
+    pub kubectl: ToolChecksums,
+    pub kfctl: ToolChecksums,
+    pub kustomize: ToolChecksums,
+    pub k9s: ToolChecksums,
+    /// The checksum of the docker convenience script (which is not versioned).
+    pub docker_script: Option<String>,
+}
+
+/// The checksums of a tool's release assets, per version, and then per architecture (since the assets differ).
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ToolChecksums(BTreeMap<String, BTreeMap<String, String>>);
+
+impl ToolChecksums {
+    /// Returns the checksum of a version for this machine's architecture, if there is one.
+    pub fn get(&self, version: &str) -> Option<&str> {
+        self.get_for(version, Arch::current())
+    }
+
+    fn get_for(&self, version: &str, arch: Arch) -> Option<&str> {
+        self.0.get(version)?.get(&arch.to_string()).map(String::as_str)
+    }
+}
+
+// Loading.
+
+impl Config {
+    /// Loads the user config (`$XDG_CONFIG_HOME/kfpl/config.toml`), and then the project config (`./kfpl.toml`)
+    /// on top of it.  If a `profile` is given, its settings are applied last.  Values missing from all of these keep
+    /// their built-in defaults.
+    pub fn load(profile: Option<&str>) -> Result<Self> {
+        let mut merged = Value::Table(Default::default());
+
+        for path in config_paths() {
+            if !path.is_file() {
+                continue;
+            }
+
+            let text = fs::read_to_string(&path).with_context(|| format!("Unable to read the config file `{}`.", path.display()))?;
+            let value = text.parse::<Value>().with_context(|| format!("Unable to parse the config file `{}`.", path.display()))?;
+
+            merge(&mut merged, value);
+        }
+
+        let raw_profiles = match merged.as_table_mut().and_then(|t| t.remove(PROFILES_KEY)) {
+            Some(Value::Table(t)) => t,
+            Some(_) => return Err(anyhow!("The `{}` config key must be a table.", PROFILES_KEY)),
+            None => Default::default(),
+        };
+
+        let mut profiles = BTreeMap::new();
+        let mut active = None;
+
+        for (name, raw_profile) in raw_profiles {
+            let mut overlay = match raw_profile {
+                Value::Table(t) => t,
+                _ => return Err(anyhow!("The profile `{}` must be a table.", name)),
+            };
+
+            let description = match overlay.remove(DESCRIPTION_KEY) {
+                Some(Value::String(d)) => Some(d),
+                Some(_) => return Err(anyhow!("The description of profile `{}` must be a string.", name)),
+                None => None,
+            };
+
+            let mut resolved = merged.clone();
+            merge(&mut resolved, Value::Table(overlay));
+
+            if Some(name.as_str()) == profile {
+                active = Some(resolved.clone());
+            }
+
+            let config = resolved.try_into().with_context(|| format!("The profile `{}` contains invalid settings.", name))?;
+
+            profiles.insert(name, Profile { description, config });
+        }
+
+        let mut config: Config = match (profile, active) {
+            (Some(_), Some(resolved)) => resolved.try_into()?,
+            (Some(name), None) => return Err(anyhow!("The profile `{}` is not defined (check out `kfpl profile list`).", name)),
+            (None, _) => merged.try_into().context("The config files contain invalid settings.")?,
+        };
+
+        config.profile = profile.map(str::to_owned);
+        config.profiles = profiles;
+
+        Ok(config)
+    }
+}
+
+/// Returns the config file locations, ordered from lowest to highest precedence.
+fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    let user_config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")));
+
+    if let Some(dir) = user_config_dir {
+        paths.push(dir.join(USER_CONFIG));
+    }
+
+    paths.push(PathBuf::from(LOCAL_CONFIG));
+
+    paths
+}
+
+/// Recursively merges `overlay` into `base`, with tables merged key-by-key and every other value replaced.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base_table), Value::Table(overlay_table)) => {
+            for (key, value) in overlay_table {
+                match base_table.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => { base_table.insert(key, value); }
+                }
+            }
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_are_picked_per_architecture() {
+        let config: ChecksumsConfig = toml::from_str(r#"
+            [kubectl."v1.19.2"]
+            amd64 = "amd64-sha256"
+            arm64 = "arm64-sha256"
+
+            [k9s."v0.22.1"]
+            amd64 = "amd64-sha256"
+        "#).unwrap();
+
+        assert_eq!(config.kubectl.get_for("v1.19.2", Arch::Amd64), Some("amd64-sha256"));
+        assert_eq!(config.kubectl.get_for("v1.19.2", Arch::Arm64), Some("arm64-sha256"));
+        assert_eq!(config.k9s.get_for("v0.22.1", Arch::Arm64), None);
+        assert_eq!(config.kubectl.get_for("v1.20.0", Arch::Amd64), None);
+    }
+
+    #[test]
+    fn kfp_sdk_follows_the_backend_unless_pinned() {
+        let versions = VersionsConfig::default();
+
+        assert_eq!(versions.kfp_sdk("1.0.4"), "1.0.4");
+        assert_eq!(versions.kfp_sdk("1.4.0"), "1.4.0");
+
+        let versions: VersionsConfig = toml::from_str(r#"kfp = "1.4.1""#).unwrap();
+
+        assert_eq!(versions.kfp_sdk("1.4.0"), "1.4.1");
+    }
+}
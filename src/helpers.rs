@@ -1,6 +1,8 @@
-use anyhow::{Context, Result, anyhow};
-use tokio::process::Command;
-use std::process::ExitStatus;
+use anyhow::{Result, anyhow};
+use std::{env, path::{Path, PathBuf}, process::ExitStatus};
+use nix::unistd::Uid;
+
+use crate::services::command::{Cmd, Runner};
 
 pub trait ExitStatusIntoUnit {
     fn status_to_unit(self) -> Result<()>;
@@ -15,14 +17,60 @@ impl ExitStatusIntoUnit for Result<ExitStatus, std::io::Error> {
     }
 }
 
-pub async fn is_docker() -> Result<bool> {
-    let status = Command::new("grep")
-        .arg("docker")
-        .arg("/proc/1/cgroup")
-        .status().await
-        .context("Unable to check if inside a docker container.")?;
+pub static SYSTEM_PREFIX: &str = "/usr/local/bin";
+
+/// Returns `~/.local/bin` (which is also where `pip3 install --user` puts its scripts).
+pub fn user_prefix() -> Option<String> {
+    env::var("HOME").ok().map(|h| format!("{}/.local/bin", h.trim_end_matches('/')))
+}
+
+/// Returns the directory that the tools are installed into by default: `/usr/local/bin` for root, and otherwise
+/// `~/.local/bin` (which needs no sudo).
+pub fn default_prefix() -> String {
+    if Uid::effective().is_root() {
+        return SYSTEM_PREFIX.to_owned();
+    }
+
+    user_prefix().unwrap_or_else(|| SYSTEM_PREFIX.to_owned())
+}
+
+/// Returns `$XDG_DATA_HOME/kfpl` (or `~/.local/share/kfpl`), where kfpl keeps what it manages (e.g., the kfp venv).
+pub fn data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+        .map(|d| d.join("kfpl"))
+}
+
+/// Returns `$XDG_CACHE_HOME/kfpl` (or `~/.cache/kfpl`), where kfpl keeps what it can recreate (e.g., unpacked bundles).
+#[cfg(not(test))]
+pub fn cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+        .map(|d| d.join("kfpl"))
+}
+
+/// Expands a leading `~` (which the shell does not expand within config files).
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home.trim_end_matches('/'), rest),
+        _ => path.to_owned()
+    }
+}
+
+pub fn is_on_path(dir: &str) -> bool {
+    let dir = Path::new(dir.trim_end_matches('/'));
 
-    println!("{:?}", status);
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|p| p == dir))
+        .unwrap_or(false)
+}
 
-    Ok(status.success())
+pub async fn is_docker(runner: &Runner) -> Result<bool> {
+    Cmd::new("grep")
+        .arg("docker")
+        .arg("/proc/1/cgroup")
+        .context("Unable to check if inside a docker container.")
+        .succeeds(runner).await
 }
\ No newline at end of file
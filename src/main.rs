@@ -2,9 +2,13 @@
 
 mod helpers;
 mod services;
+mod config;
+mod state;
+mod bundle;
 
 use simple_logger::SimpleLogger;
-use anyhow::{Result, Error};
+use std::{path::Path, time::Duration};
+use anyhow::{Result, Error, Context, anyhow};
 use log::{
     warn, 
     LevelFilter
@@ -12,8 +16,14 @@ use log::{
 use clap::{Arg, App, SubCommand, ArgMatches};
 use yansi::Paint;
 
+use crate::config::{Config, VersionsConfig};
+use crate::state::State;
+use crate::bundle::{Bundle, Manifest};
 use crate::services::{
-    model::{EnsurableEntity, RemovableEntity},
+    model::{ClusterProvider, EnsurableEntity, RemovableEntity, InspectableEntity, UpgradableEntity, BundleableEntity, Status},
+    command,
+    checksum,
+    platform::Arch,
     docker::Docker,
     k3d::K3d,
     kubectl::Kubectl,
@@ -21,17 +31,25 @@ use crate::services::{
     kfp::Kfp,
     kfp_service::KfpService,
     port_forward::PortForward,
+    ingress::Ingress,
+    registry::Registry,
     k3d_service::K3dService,
-    curl::Curl,
+    kind_service::KindService,
+    minikube_service::MinikubeService,
+    provider::{Provider, PROVIDERS},
     git::Git,
     kfctl::Kfctl,
-    kf_service::KfService,
+    kf_service::{self, KfService, KF_COMPONENTS},
+    kf_installer::{KfInstaller, KF_INSTALLERS},
+    kustomize::Kustomize,
     k9s::K9s
 };
 
+static UPGRADABLE: [&str; 6] = ["k3d", "kubectl", "kfctl", "kustomize", "k9s", "kfp"];
+static UNINSTALLABLE: [&str; 8] = ["k3d", "kubectl", "kfctl", "kustomize", "k9s", "kfp", "docker", "pip3"];
+static SYSTEM_PACKAGES: [&str; 2] = ["docker", "pip3"];
+
 // TODO:
-//   * Make all of the version, yaml location, blah, blah, blah, configurable.
-//   * Pass through options to k3d.
 //   * Fix `kfpl service` bug.
 
 #[tokio::main]
@@ -39,7 +57,9 @@ async fn main() -> Result<()> {
     // Set up logging.
     SimpleLogger::new().with_level(LevelFilter::Info);
 
-    let init_help = &*format!("Ensures the dependencies are met ({}).", Paint::yellow("may need to be run as sudo"));
+    let init_help = &*format!("Ensures the dependencies are met ({}).", Paint::yellow("may need to be run as sudo for the system packages, or a system install prefix"));
+    let uninstall_help = &*format!("Removes the dependencies that kfpl installed ({}).", Paint::yellow("may need to be run as sudo for the system packages, or a system install prefix"));
+    let upgrade_help = &*format!("Reinstalls the tools whose version differs from the pinned version [config: `versions`] ({}).", Paint::yellow("may need to be run as sudo for a system install prefix"));
 
     let app = App::new("kfpl")
         .version("1.2.0")
@@ -49,74 +69,248 @@ async fn main() -> Result<()> {
             .short("-y")
             .long("yes")
             .help("Answers all of the prompts with 'yes', resulting in a no-touch execution."))
+        .arg(Arg::with_name("dry_run")
+            .long("dry-run")
+            .help("Prints the commands that would be executed to make things present, without executing any of them."))
+        .arg(Arg::with_name("profile")
+            .long("profile")
+            .takes_value(true)
+            .env("KFPL_PROFILE")
+            .help("The named profile (from the config files) to apply on top of the base settings."))
+        .arg(Arg::with_name("prefix")
+            .long("prefix")
+            .takes_value(true)
+            .env("KFPL_PREFIX")
+            .help("The directory that the tools are installed into [config: `install.prefix`, default: `/usr/local/bin` for root, and otherwise `~/.local/bin`]."))
+        .arg(Arg::with_name("bundle")
+            .long("bundle")
+            .takes_value(true)
+            .env("KFPL_BUNDLE")
+            .help("Installs from an offline bundle (created by `kfpl bundle create`), rather than the internet (applies to `init` and `service start`)."))
         .subcommand(SubCommand::with_name("init")
-            .about(init_help))
+            .about(init_help)
+            .arg(Arg::with_name("remove")
+                .long("remove")
+                .help("Removes the dependencies that kfpl installed, rather than ensuring them (same as `kfpl uninstall`).")))
+        .subcommand(SubCommand::with_name("uninstall")
+            .about(uninstall_help)
+            .arg(Arg::with_name("only")
+                .long("only")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .possible_values(&UNINSTALLABLE)
+                .help("Restricts the removal to the given tools (e.g., `--only kubectl,k9s`)."))
+            .arg(Arg::with_name("include_system")
+                .long("include-system")
+                .help("Also removes `docker` and `pip3` (if kfpl installed them), which other software on the machine may depend on (implied when `--only` names them).")))
         .subcommand(SubCommand::with_name("service")
-            .about("Commands to interact with the k3d cluster, and the KFP service.")
+            .about("Commands to interact with the k8s cluster, and the KFP service.")
             .subcommand(SubCommand::with_name("start")
                 .about("Starts the k8s cluster, and the KFP service.")
                 .arg(Arg::with_name("kfp_only")
                     .long("kfp-only")
-                    .help("Deploys only KubeFlow Pipelines (KFP), rather than all of KubeFlow."))
+                    .help("Deploys only KubeFlow Pipelines (KFP), rather than all of KubeFlow [config: `service.kfp_only`]."))
+                .arg(Arg::with_name("no_kfp_only")
+                    .long("no-kfp-only")
+                    .conflicts_with("kfp_only")
+                    .help("Deploys all of KubeFlow, even when the config sets `service.kfp_only`."))
+                .arg(Arg::with_name("provider")
+                    .long("provider")
+                    .takes_value(true)
+                    .possible_values(&PROVIDERS)
+                    .help("The tool that creates the k8s cluster (the `k3d_*` options other than the name only apply to k3d) [config: `service.provider`, default: `k3d`]."))
                 .arg(Arg::with_name("k3d_cluster_name")
                     .short("n")
                     .long("k3d-cluster-name")
                     .takes_value(true)
-                    .default_value("kfp-local")
-                    .help("The `name` assigned to the cluster created by k3d."))
+                    .help("The `name` assigned to the cluster (by any provider) [config: `service.k3d_cluster_name`, default: `kfp-local`]."))
                 .arg(Arg::with_name("k3d_image")
                     .short("i")
                     .long("k3d-image")
                     .takes_value(true)
-                    .default_value("rancher/k3s:v1.20.2-k3s1")
-                    .help("The `k3s` image used to serve the k3d cluster."))
+                    .help("The `k3s` image used to serve the k3d cluster [config: `service.k3d_image`, default: `rancher/k3s:v1.20.2-k3s1`]."))
                 .arg(Arg::with_name("k3d_api_address")
                     .short("a")
                     .long("k3d-api-address")
                     .takes_value(true)
-                    .default_value("0.0.0.0")
-                    .help("The address to which the k3d load balancer for the kubernetes API is bound (e.g., if you don't want outside connections, use `127.0.0.1`)."))
+                    .help("The address to which the k3d load balancer for the kubernetes API is bound (e.g., if you don't want outside connections, use `127.0.0.1`) [config: `service.k3d_api_address`, default: `0.0.0.0`]."))
                 .arg(Arg::with_name("k3d_api_port")
                     .short("p")
                     .long("k3d-api-port")
                     .takes_value(true)
-                    .default_value("6443")
-                    .help("The port to which the k3d load balancer for the kubernetes API is bound."))
+                    .help("The port to which the k3d load balancer for the kubernetes API is bound [config: `service.k3d_api_port`, default: `6443`]."))
+                .arg(Arg::with_name("k3d_servers")
+                    .long("k3d-servers")
+                    .takes_value(true)
+                    .help("The number of k3s servers in the k3d cluster [config: `service.k3d_servers`, default: k3d's]."))
+                .arg(Arg::with_name("k3d_agents")
+                    .long("k3d-agents")
+                    .takes_value(true)
+                    .help("The number of k3s agents in the k3d cluster [config: `service.k3d_agents`, default: k3d's]."))
+                .arg(Arg::with_name("k3d_ports")
+                    .long("k3d-port")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Maps a port into the k3d cluster (e.g., `8080:80@loadbalancer`), and can be repeated [config: `service.k3d_ports`]."))
+                .arg(Arg::with_name("k3d_volumes")
+                    .long("k3d-volume")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Mounts a volume into the k3d nodes (e.g., `/data:/data`), and can be repeated [config: `service.k3d_volumes`]."))
+                .arg(Arg::with_name("k3s_server_args")
+                    .long("k3s-server-arg")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .allow_hyphen_values(true)
+                    .help("Passes an argument to the k3s servers (e.g., `--disable-network-policy`), and can be repeated [config: `service.k3s_server_args`]."))
+                .arg(Arg::with_name("k3d_env")
+                    .long("k3d-env")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Sets an environment variable on the k3d nodes (e.g., `HTTP_PROXY=http://proxy:3128`), and can be repeated [config: `service.k3d_env`]."))
+                .arg(Arg::with_name("k3d_labels")
+                    .long("k3d-label")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Labels the k3d node containers (e.g., `team=ml`), and can be repeated [config: `service.k3d_labels`]."))
+                .arg(Arg::with_name("k3d_extra_args")
+                    .multiple(true)
+                    .last(true)
+                    .help("Arguments after `--` are appended, verbatim, to `k3d cluster create` [config: `service.k3d_extra_args`]."))
+                .arg(Arg::with_name("expose_port")
+                    .long("expose-port")
+                    .takes_value(true)
+                    .help("Maps the `port` on the k3d load balancer to the UI (through a traefik ingress), so it is reachable without `kfpl ui` [config: `service.expose_port`]."))
+                .arg(Arg::with_name("registry")
+                    .long("registry")
+                    .help("Creates (or reuses) a local registry, and connects the k3d cluster to it, so that pushed images can be pulled [config: `service.registry`]."))
+                .arg(Arg::with_name("no_registry")
+                    .long("no-registry")
+                    .conflicts_with("registry")
+                    .help("Leaves out the local registry, even when the config sets `service.registry`."))
+                .arg(Arg::with_name("registry_name")
+                    .long("registry-name")
+                    .takes_value(true)
+                    .help("The `name` of the local registry (k3d prefixes the container with `k3d-`) [config: `service.registry_name`, default: `kfpl-registry`]."))
+                .arg(Arg::with_name("registry_port")
+                    .long("registry-port")
+                    .takes_value(true)
+                    .help("The host port of the local registry [config: `service.registry_port`, default: `5000`]."))
+                .arg(Arg::with_name("wait_timeout")
+                    .long("wait-timeout")
+                    .takes_value(true)
+                    .help("How many `seconds` to wait for the cluster, and then the deployment, to become ready [config: `service.wait_timeout`, default: `600`]."))
                 .arg(Arg::with_name("kfp_version")
                     .long("kfp-version")
                     .takes_value(true)
-                    .default_value("1.0.4")
-                    .help("The specific version of KFP to install (only works with the `--kfp-only` option)."))
+                    .help("The specific version of KFP to install (only works with the `--kfp-only` option) [config: `service.kfp_version`, default: `1.0.4`]."))
+                .arg(Arg::with_name("kf_installer")
+                    .long("kf-installer")
+                    .takes_value(true)
+                    .possible_values(&KF_INSTALLERS)
+                    .help("How KF is deployed: from the `kubeflow/manifests` example with kustomize (v1.3+), or from a kfdef with kfctl (v1.2 and earlier) [config: `service.kf_installer`, default: `manifests`]."))
+                .arg(Arg::with_name("kf_manifests_version")
+                    .long("kf-manifests-version")
+                    .takes_value(true)
+                    .help("The `tag` of `kubeflow/manifests` to deploy (only applies to the manifests installer) [config: `service.kf_manifests_version`, default: `v1.3.0`]."))
+                .arg(Arg::with_name("components")
+                    .long("components")
+                    .takes_value(true)
+                    .multiple(true)
+                    .use_delimiter(true)
+                    .possible_values(&KF_COMPONENTS)
+                    .help("Deploys only the given KF components, along with their dependencies (istio, cert-manager, dex, and profiles), e.g., `--components pipelines,notebooks` (only applies to the manifests installer) [config: `service.components`, default: every one]."))
                 .arg(Arg::with_name("kf_yaml")
                     .long("kf-yaml")
                     .takes_value(true)
-                    .default_value("https://raw.githubusercontent.com/kubeflow/manifests/v1.2-branch/kfdef/kfctl_k8s_istio.v1.2.0.yaml")
-                    .help("The specific YAML manifest used to deploy KF (is ignored when `--kfp-only` is set).")))
+                    .help("The specific kfdef YAML manifest used to deploy KF (only applies to the kfctl installer) [config: `service.kf_yaml`].")))
             .subcommand(SubCommand::with_name("stop")
                 .about("Stops the k8s cluster, and the KFP service.")
+                .arg(Arg::with_name("provider")
+                    .long("provider")
+                    .takes_value(true)
+                    .possible_values(&PROVIDERS)
+                    .help("The tool that created the k8s cluster [config: `service.provider`, default: `k3d`]."))
                 .arg(Arg::with_name("k3d_cluster_name")
                     .short("n")
                     .long("k3d-cluster-name")
                     .takes_value(true)
-                    .default_value("kfp-local")
-                    .help("The `name` assigned to the cluster created by k3d."))))
+                    .help("The `name` assigned to the cluster (by any provider) [config: `service.k3d_cluster_name`, default: `kfp-local`]."))))
         .subcommand(SubCommand::with_name("ui")
             .about("Starts the port forwarding to the KFP UI via `kubectl`.")
             .arg(Arg::with_name("kfp_only")
                 .long("kfp-only")
-                .help("Port forwards only the KubeFlow Pipelines (KFP) UI, rather than the KubeFlow UI."))
+                .help("Port forwards only the KubeFlow Pipelines (KFP) UI, rather than the KubeFlow UI [config: `ui.kfp_only`]."))
+            .arg(Arg::with_name("no_kfp_only")
+                .long("no-kfp-only")
+                .conflicts_with("kfp_only")
+                .help("Port forwards the KubeFlow UI, even when the config sets `ui.kfp_only`."))
             .arg(Arg::with_name("port")
                 .short("p")
                 .long("port")
                 .takes_value(true)
-                .default_value("8080")
-                .help("The localhost port to which you want to bind the port forward."))
+                .help("The localhost port to which you want to bind the port forward [config: `ui.port`, default: `8080`]."))
             .arg(Arg::with_name("address")
                 .short("a")
                 .long("address")
                 .takes_value(true)
-                .default_value("0.0.0.0")
-                .help("The address to which the port forwarding proxy is bound (e.g., if you don't want outside connections, use `127.0.0.1`).")));
+                .help("The address to which the port forwarding proxy is bound (e.g., if you don't want outside connections, use `127.0.0.1`) [config: `ui.address`, default: `0.0.0.0`].")))
+        .subcommand(SubCommand::with_name("upgrade")
+            .about(upgrade_help)
+            .arg(Arg::with_name("only")
+                .long("only")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .possible_values(&UPGRADABLE)
+                .help("Restricts the upgrade to the given tools (e.g., `--only kubectl,k9s`).")))
+        .subcommand(SubCommand::with_name("status")
+            .about("Reports the presence of the dependencies, the k3d cluster, the KFP service, and the port forward.")
+            .arg(Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .possible_values(&["table", "json"])
+                .default_value("table")
+                .help("The format of the report.")))
+        .subcommand(SubCommand::with_name("bundle")
+            .about("Commands to work with offline (air-gapped) bundles.")
+            .subcommand(SubCommand::with_name("create")
+                .about("Downloads the pinned tools, the kfp SDK, the KFP (and KubeFlow) manifests, and every image they use into one archive.")
+                .arg(Arg::with_name("path")
+                    .required(true)
+                    .help("Where the bundle archive is written (e.g., `kfpl-bundle.tar`)."))
+                .arg(Arg::with_name("kfp_only")
+                    .long("kfp-only")
+                    .help("Bundles only KubeFlow Pipelines (KFP), rather than the KubeFlow manifests as well (`service.kf_manifests_version`, with the `service.components`) [config: `service.kfp_only`]."))
+                .arg(Arg::with_name("no_kfp_only")
+                    .long("no-kfp-only")
+                    .conflicts_with("kfp_only")
+                    .help("Bundles the KubeFlow manifests as well, even when the config sets `service.kfp_only`."))))
+        .subcommand(SubCommand::with_name("registry")
+            .about("Commands to manage the local registry (see `service start --registry`).")
+            .subcommand(SubCommand::with_name("status")
+                .about("Reports whether the local registry is running, and where to push to.")
+                .arg(Arg::with_name("registry_name")
+                    .long("registry-name")
+                    .takes_value(true)
+                    .help("The `name` of the local registry [config: `service.registry_name`, default: `kfpl-registry`].")))
+            .subcommand(SubCommand::with_name("delete")
+                .about("Deletes the local registry (and every image pushed to it).")
+                .arg(Arg::with_name("registry_name")
+                    .long("registry-name")
+                    .takes_value(true)
+                    .help("The `name` of the local registry [config: `service.registry_name`, default: `kfpl-registry`]."))))
+        .subcommand(SubCommand::with_name("profile")
+            .about("Commands to inspect the profiles defined in the config files.")
+            .subcommand(SubCommand::with_name("list")
+                .about("Lists the profiles, and the cluster and UI each one sets up.")));
         
     execute(app).await?;
 
@@ -128,34 +322,250 @@ async fn main() -> Result<()> {
 async fn execute(mut app: App<'_, '_>) -> Result<()> {
     let args = app.clone().get_matches();
     let confirm = !args.is_present("skip_confirm");
+    let mut config = Config::load(args.value_of("profile"))?;
+
+    // Command line values take precedence over the config files.
+    if let Some(prefix) = args.value_of("prefix") {
+        config.install.prefix = prefix.to_owned();
+    }
+    config.install.prefix = helpers::expand_home(&config.install.prefix);
+
+    command::set_dry_run(args.is_present("dry_run"));
+    checksum::set_required(config.checksums.require);
+
+    let offline_bundle = args.value_of("bundle").map(Bundle::open).transpose()?;
+    if let Some(b) = &offline_bundle {
+        b.apply(&mut config);
+    }
+    let bundle_dir = offline_bundle.as_ref().map(Bundle::dir);
     let (sub_name, sub_matches) = args.subcommand();
 
     match sub_name {
-        "init" => init(confirm).await,
-        "service" => service(confirm, sub_matches.unwrap()).await,
-        "ui" => ui(confirm, sub_matches.unwrap()).await,
+        "init" if sub_matches.unwrap().is_present("remove") => uninstall(confirm, &config, &[], false).await,
+        "init" => init(confirm, &config, bundle_dir).await,
+        "uninstall" => {
+            let args = sub_matches.unwrap();
+            let only: Vec<&str> = args.values_of("only").map(|v| v.collect()).unwrap_or_default();
+
+            uninstall(confirm, &config, &only, args.is_present("include_system")).await
+        },
+        "upgrade" => upgrade(confirm, &config, sub_matches.unwrap()).await,
+        "service" => service(confirm, &config, offline_bundle.as_ref(), sub_matches.unwrap()).await,
+        "bundle" => bundle(&config, sub_matches.unwrap()).await,
+        "ui" => ui(confirm, &config, sub_matches.unwrap()).await,
+        "status" => status(&config, sub_matches.unwrap()).await,
+        "registry" => registry(confirm, &config, sub_matches.unwrap()).await,
+        "profile" => profile(&config, sub_matches.unwrap()),
         _ => app.print_long_help().map_err(|e| e.into())
     }
 }
 
-async fn init(confirm: bool) -> Result<()> {
+async fn init(confirm: bool, config: &Config, bundle_dir: Option<&Path>) -> Result<()> {
     println!("Ensuring proper {} ...", Paint::blue("dependencies"));
 
-    Curl::default().ensure(confirm).await?;
+    let versions = &config.versions;
+    let checksums = &config.checksums;
+    let prefix = &config.install.prefix;
+
+    warn_if_not_on_path(prefix);
+
     Git::default().ensure(confirm).await?;
-    K3d::default().ensure(confirm).await?;
-    Kubectl::default().ensure(confirm).await?;
-    Kfctl::default().ensure(confirm).await?;
-    K9s::default().ensure(confirm).await?;
+
+    K3d::default()
+        .with_version(&versions.k3d)
+        .with_sha256(checksums.k3d.get(&versions.k3d))
+        .with_prefix(prefix)
+        .with_bundle(bundle_dir)
+        .ensure(confirm).await?;
+
+    Kubectl::default()
+        .with_version(&versions.kubectl)
+        .with_sha256(checksums.kubectl.get(&versions.kubectl))
+        .with_prefix(prefix)
+        .with_bundle(bundle_dir)
+        .ensure(confirm).await?;
+
+    // Only the tool that deploys KubeFlow (with the configured installer) is needed.
+    match config.service.kf_installer {
+        KfInstaller::Manifests => Kustomize::default()
+            .with_version(&versions.kustomize)
+            .with_sha256(checksums.kustomize.get(&versions.kustomize))
+            .with_prefix(prefix)
+            .with_bundle(bundle_dir)
+            .ensure(confirm).await?,
+        KfInstaller::Kfctl => {
+            let kfctl = Kfctl::default()
+                .with_version(&versions.kfctl)
+                .with_sha256(checksums.kfctl.get(&versions.kfctl))
+                .with_prefix(prefix)
+                .with_bundle(bundle_dir);
+
+            if kfctl.is_released() {
+                kfctl.ensure(confirm).await?;
+            } else {
+                warn_kfctl_not_released();
+            }
+        }
+    }
+
+    K9s::default()
+        .with_version(&versions.k9s)
+        .with_sha256(checksums.k9s.get(&versions.k9s))
+        .with_prefix(prefix)
+        .with_bundle(bundle_dir)
+        .ensure(confirm).await?;
+
     Pip3::default().ensure(confirm).await?;
-    Kfp::default().ensure(confirm).await?;
-    Docker::default().ensure(confirm).await?;
+
+    Kfp::default()
+        .with_version(kfp_sdk(versions, &config.service.kfp_version))
+        .with_server_version(&config.service.kfp_version)
+        .with_prefix(prefix)
+        .with_bundle(bundle_dir)
+        .ensure(confirm).await?;
+
+    Docker::default().with_script_sha256(checksums.docker_script.as_deref()).ensure(confirm).await?;
 
     Ok(())
 }
 
-async fn service(confirm: bool, args: &ArgMatches<'_>) -> Result<()> {
+async fn uninstall(confirm: bool, config: &Config, only: &[&str], include_system: bool) -> Result<()> {
+    println!("Removing the {} that kfpl installed ...", Paint::blue("dependencies"));
+
+    let state = State::load()?;
+
+    // The tools are removed from wherever they were installed (the prefix may have changed since).
+    let prefix = |name: &str| state.installs.get(name)
+        .and_then(|i| i.paths.first())
+        .and_then(|p| Path::new(p).parent())
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|| config.install.prefix.clone());
+
+    let selected = |name: &str| {
+        if !only.is_empty() && !only.contains(&name) {
+            return false;
+        }
+
+        if !state.installs.contains_key(name) {
+            println!("Skipping `{}` ({}).", Paint::blue(name), Paint::yellow("not installed by kfpl"));
+            return false;
+        }
+
+        true
+    };
+
+    if selected("kfp") {
+        Kfp::default().with_prefix(&prefix("kfp")).remove(confirm).await?;
+    }
+
+    if selected("k9s") {
+        K9s::default().with_prefix(&prefix("k9s")).remove(confirm).await?;
+    }
+
+    if selected("kustomize") {
+        Kustomize::default().with_prefix(&prefix("kustomize")).remove(confirm).await?;
+    }
+
+    if selected("kfctl") {
+        Kfctl::default().with_prefix(&prefix("kfctl")).remove(confirm).await?;
+    }
+
+    if selected("kubectl") {
+        Kubectl::default().with_prefix(&prefix("kubectl")).remove(confirm).await?;
+    }
+
+    if selected("k3d") {
+        K3d::default().with_prefix(&prefix("k3d")).remove(confirm).await?;
+    }
+
+    // Naming a system package in `--only` is as explicit as `--include-system`.
+    let include_system = include_system || only.iter().any(|name| SYSTEM_PACKAGES.contains(name));
+
+    if include_system {
+        println!("{}", Paint::yellow("Removing system packages: other software on this machine may depend on them."));
+
+        if selected("pip3") {
+            Pip3::default().remove(confirm).await?;
+        }
+
+        if selected("docker") {
+            Docker::default().remove(confirm).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn upgrade(confirm: bool, config: &Config, args: &ArgMatches<'_>) -> Result<()> {
+    println!("Upgrading the {} to their pinned versions ...", Paint::blue("dependencies"));
+
+    let versions = &config.versions;
+    let checksums = &config.checksums;
+    let prefix = &config.install.prefix;
+    let only: Vec<&str> = args.values_of("only").map(|v| v.collect()).unwrap_or_default();
+    let selected = |name: &str| only.is_empty() || only.contains(&name);
+
+    warn_if_not_on_path(prefix);
+
+    if selected("k3d") {
+        K3d::default()
+            .with_version(&versions.k3d)
+            .with_sha256(checksums.k3d.get(&versions.k3d))
+            .with_prefix(prefix)
+            .upgrade(confirm).await?;
+    }
+
+    if selected("kubectl") {
+        Kubectl::default()
+            .with_version(&versions.kubectl)
+            .with_sha256(checksums.kubectl.get(&versions.kubectl))
+            .with_prefix(prefix)
+            .upgrade(confirm).await?;
+    }
+
+    if selected("kfctl") {
+        let kfctl = Kfctl::default()
+            .with_version(&versions.kfctl)
+            .with_sha256(checksums.kfctl.get(&versions.kfctl))
+            .with_prefix(prefix);
+
+        if kfctl.is_released() {
+            kfctl.upgrade(confirm).await?;
+        } else {
+            warn_kfctl_not_released();
+        }
+    }
+
+    if selected("kustomize") {
+        Kustomize::default()
+            .with_version(&versions.kustomize)
+            .with_sha256(checksums.kustomize.get(&versions.kustomize))
+            .with_prefix(prefix)
+            .upgrade(confirm).await?;
+    }
+
+    if selected("k9s") {
+        K9s::default()
+            .with_version(&versions.k9s)
+            .with_sha256(checksums.k9s.get(&versions.k9s))
+            .with_prefix(prefix)
+            .upgrade(confirm).await?;
+    }
+
+    if selected("kfp") {
+        Kfp::default()
+            .with_version(kfp_sdk(versions, &config.service.kfp_version))
+            .with_server_version(&config.service.kfp_version)
+            .with_prefix(prefix)
+            .upgrade(confirm).await?;
+    }
+
+    Ok(())
+}
+
+async fn service(confirm: bool, config: &Config, bundle: Option<&Bundle>, args: &ArgMatches<'_>) -> Result<()> {
     let (sub_name, sub_args_option) = args.subcommand();
+    let bundle_dir = bundle.map(Bundle::dir);
 
     let sub_args = sub_args_option.unwrap();
 
@@ -163,40 +573,158 @@ async fn service(confirm: bool, args: &ArgMatches<'_>) -> Result<()> {
         "start" => {
             println!("Ensuring {} are running ...", Paint::blue("services"));
         
-            // SAFETY: unwrap is safe because it has a default value.
-            let k3d_cluster_name = sub_args.value_of("k3d_cluster_name").unwrap();
-            let k3d_image = sub_args.value_of("k3d_image").unwrap();
-            let k3d_api_address = sub_args.value_of("k3d_api_address").unwrap();
-            let k3d_api_port = sub_args.value_of("k3d_api_port").unwrap();
-            let kfp_version = sub_args.value_of("kfp_version").unwrap();
-            let kf_yaml = sub_args.value_of("kf_yaml").unwrap();
-
-            K3dService::default()
-                .with_k3d_cluster_name(k3d_cluster_name)
+            // Command line values take precedence over the config files.
+            let kfp_only = switch(sub_args, "kfp_only", config.service.kfp_only);
+            let provider = sub_args.value_of("provider").map(str::parse).transpose()?.unwrap_or(config.service.provider);
+            let k3d_cluster_name = sub_args.value_of("k3d_cluster_name").unwrap_or(&config.service.k3d_cluster_name);
+            let k3d_image = sub_args.value_of("k3d_image").unwrap_or(&config.service.k3d_image);
+            let k3d_api_address = sub_args.value_of("k3d_api_address").unwrap_or(&config.service.k3d_api_address);
+            let k3d_api_port = sub_args.value_of("k3d_api_port").map(str::to_owned).unwrap_or_else(|| config.service.k3d_api_port.to_string());
+            let k3d_servers = sub_args.value_of("k3d_servers").map(|s| s.parse().context("The `--k3d-servers` option has to be a number.")).transpose()?.or(config.service.k3d_servers);
+            let k3d_agents = sub_args.value_of("k3d_agents").map(|a| a.parse().context("The `--k3d-agents` option has to be a number.")).transpose()?.or(config.service.k3d_agents);
+            let values = |name: &str, configured: &[String]| sub_args.values_of(name).map(|v| v.map(str::to_owned).collect()).unwrap_or_else(|| configured.to_vec());
+            let k3d_volumes: Vec<String> = values("k3d_volumes", &config.service.k3d_volumes);
+            let k3s_server_args: Vec<String> = values("k3s_server_args", &config.service.k3s_server_args);
+            let k3d_env: Vec<String> = values("k3d_env", &config.service.k3d_env);
+            let k3d_labels: Vec<String> = values("k3d_labels", &config.service.k3d_labels);
+            let mut k3d_ports: Vec<String> = values("k3d_ports", &config.service.k3d_ports);
+            let k3d_extra_args: Vec<String> = values("k3d_extra_args", &config.service.k3d_extra_args);
+            let expose_port: Option<u16> = sub_args.value_of("expose_port").map(|p| p.parse().context("The `--expose-port` option has to be a number.")).transpose()?.or(config.service.expose_port);
+            let registry = switch(sub_args, "registry", config.service.registry);
+            let registry_name = sub_args.value_of("registry_name").unwrap_or(&config.service.registry_name);
+            let registry_port: u16 = sub_args.value_of("registry_port").map(|p| p.parse().context("The `--registry-port` option has to be a number.")).transpose()?.unwrap_or(config.service.registry_port);
+            let wait_timeout = sub_args.value_of("wait_timeout").map(|t| t.parse().context("The `--wait-timeout` option has to be a number.")).transpose()?.unwrap_or(config.service.wait_timeout);
+            let wait_timeout = Some(Duration::from_secs(wait_timeout));
+            let kfp_version = sub_args.value_of("kfp_version").unwrap_or(&config.service.kfp_version);
+            let kf_installer = sub_args.value_of("kf_installer").map(str::parse).transpose()?.unwrap_or(config.service.kf_installer);
+            let kf_manifests_version = sub_args.value_of("kf_manifests_version").unwrap_or(&config.service.kf_manifests_version);
+            let components: Vec<String> = values("components", &config.service.components);
+            let kf_yaml = sub_args.value_of("kf_yaml").unwrap_or(&config.service.kf_yaml);
+
+            // Only what was rendered into the bundle can be deployed out of it.
+            if let (Some(bundle), false) = (bundle, kfp_only) {
+                let manifest = &bundle.manifest;
+
+                match &manifest.kf_manifests_version {
+                    None => return Err(Error::msg("The bundle only contains KFP, so please use `--kfp-only` (or `service.kfp_only`), or create the bundle without `--kfp-only`.")),
+                    Some(version) if kf_installer != KfInstaller::Manifests || kf_manifests_version != version || components != manifest.components => {
+                        let picked = if manifest.components.is_empty() { "every component".to_owned() } else { manifest.components.join(", ") };
+                        return Err(anyhow!("The bundle contains the KubeFlow manifests {} ({}), so please deploy those with the manifests installer (or create another bundle).", version, picked));
+                    },
+                    Some(_) => {}
+                }
+            }
+
+            if !kfp_only && kf_installer == KfInstaller::Kfctl && !Kfctl::default().is_released() {
+                return Err(anyhow!("kfctl is only released for amd64 (rather than {}), so please use `--kf-installer manifests` or `--kfp-only`.", Arch::current()));
+            }
+
+            if !kfp_only && !components.is_empty() && kf_installer != KfInstaller::Manifests {
+                return Err(Error::msg("Picking the KF components needs the manifests installer (kfctl deploys every component of the kfdef), so please use `--kf-installer manifests`."));
+            }
+
+            if !kfp_only {
+                kf_service::check_components(kf_manifests_version, &components)?;
+            }
+
+            // Offline bundles carry the k3s images, the UI is exposed through the k3d load balancer, and the registry is
+            // connected through k3d's registry config.
+            let k3d_only: Vec<&str> = [(bundle_dir.is_some(), "offline bundles"), (expose_port.is_some(), "`--expose-port`"), (registry, "`--registry`")].iter()
+                .filter(|(used, _)| *used)
+                .map(|(_, option)| *option)
+                .collect();
+
+            if provider != Provider::K3d && !k3d_only.is_empty() {
+                return Err(anyhow!("The k3d provider is needed for {} (rather than {}), so please use `--provider k3d`.", k3d_only.join(", "), provider));
+            }
+
+            if let Some(port) = expose_port {
+                // Traefik listens on port 80 of the load balancer.
+                k3d_ports.push(format!("{}:80@loadbalancer", port));
+            }
+
+            // The registry outlives the cluster, so an existing one is reused.
+            let registry = if registry {
+                let registry = Registry::default()
+                    .with_registry_name(registry_name)
+                    .with_registry_port(registry_port);
+
+                registry.ensure(confirm).await?;
+
+                Some(registry)
+            } else {
+                None
+            };
+
+            let k3d = K3dService::default()
                 .with_k3d_image(k3d_image)
                 .with_k3d_api_address(k3d_api_address)
-                .with_k3d_api_port(k3d_api_port)
-                .ensure(confirm).await?;
+                .with_k3d_api_port(&k3d_api_port)
+                .with_k3d_servers(k3d_servers)
+                .with_k3d_agents(k3d_agents)
+                .with_k3d_ports(&k3d_ports)
+                .with_k3d_volumes(&k3d_volumes)
+                .with_k3s_server_args(&k3s_server_args)
+                .with_k3d_env(&k3d_env)
+                .with_k3d_labels(&k3d_labels)
+                .with_k3d_extra_args(&k3d_extra_args)
+                .with_registry(registry.as_ref().map(Registry::cluster_address).as_deref())
+                .with_wait_timeout(wait_timeout)
+                .with_bundle(bundle_dir);
 
-            if sub_args.is_present("kfp_only") {
+            ensure_cluster(&*cluster_provider(provider, k3d, k3d_cluster_name), confirm).await?;
+
+            if kfp_only {
                 KfpService::default()
                     .with_kfp_version(kfp_version)
+                    .with_k3d_cluster_name(k3d_cluster_name)
+                    .with_bundle(bundle_dir)
+                    .with_wait_timeout(wait_timeout)
+                    .ensure(confirm).await?;
+
+                // The SDK has to match the backend that was just deployed.
+                Kfp::default()
+                    .with_version(kfp_sdk(&config.versions, kfp_version))
+                    .with_server_version(kfp_version)
+                    .with_prefix(&config.install.prefix)
+                    .with_bundle(bundle_dir)
                     .ensure(confirm).await?;
             } else {
                 KfService::default()
+                    .with_installer(kf_installer)
+                    .with_manifests_version(kf_manifests_version)
+                    .with_components(&components)
                     .with_kf_yaml(kf_yaml)
+                    .with_k3d_cluster_name(k3d_cluster_name)
+                    .with_bundle(bundle_dir)
+                    .with_wait_timeout(wait_timeout)
                     .ensure(confirm).await?;
             }
+
+            if let Some(port) = expose_port {
+                Ingress::default()
+                    .with_kfp_only(kfp_only)
+                    .with_port(port)
+                    .with_k3d_cluster_name(k3d_cluster_name)
+                    .ensure(confirm).await?;
+            }
+
+            if let Some(registry) = &registry {
+                print_registry_usage(registry);
+            }
         },
         "stop" => {
             println!("Stopping {} ...", Paint::blue("services"));
 
-            // SAFETY: unwrap is safe because it has a default value.
-            let k3d_cluster_name = sub_args.value_of("k3d_cluster_name").unwrap();
+            let provider = sub_args.value_of("provider").map(str::parse).transpose()?.unwrap_or(config.service.provider);
+            let k3d_cluster_name = sub_args.value_of("k3d_cluster_name").unwrap_or(&config.service.k3d_cluster_name);
+
+            let removed = remove_cluster(&*cluster_provider(provider, K3dService::default(), k3d_cluster_name), confirm).await?;
 
-            K3dService::default()
-                .with_k3d_cluster_name(k3d_cluster_name)
-                .remove(confirm).await?;
+            // The deployments went away with the cluster, so they are forgotten along with it.
+            if removed {
+                State::update(|state| state.installs.retain(|_, install| install.options.get("k3d_cluster_name").map(String::as_str) != Some(k3d_cluster_name)))?;
+            }
         },
         _ => return Err(Error::msg("Please use a subcommand (check out `kfpl service -h` for help)."))
     }
@@ -204,19 +732,307 @@ async fn service(confirm: bool, args: &ArgMatches<'_>) -> Result<()> {
     Ok(())
 }
 
-async fn ui(confirm: bool, args: &ArgMatches<'_>) -> Result<()> {
+async fn registry(confirm: bool, config: &Config, args: &ArgMatches<'_>) -> Result<()> {
+    let (sub_name, sub_args_option) = args.subcommand();
+
+    let registry_name = sub_args_option.and_then(|a| a.value_of("registry_name")).unwrap_or(&config.service.registry_name);
+    let registry = Registry::default()
+        .with_registry_name(registry_name)
+        .with_registry_port(config.service.registry_port);
+
+    match sub_name {
+        "status" => {
+            let status = registry.status().await;
+            print_status_table(std::slice::from_ref(&status));
+
+            if !status.present {
+                return Err(anyhow!("The local registry `{}` is not running (start it with `kfpl service start --registry`).", registry_name));
+            }
+
+            print_registry_usage(&registry);
+        },
+        "delete" => {
+            println!("Deleting the local {} ...", Paint::blue("registry"));
+
+            registry.remove(confirm).await?;
+        },
+        _ => return Err(Error::msg("Please use a subcommand (check out `kfpl registry -h` for help)."))
+    }
+
+    Ok(())
+}
+
+async fn ui(confirm: bool, config: &Config, args: &ArgMatches<'_>) -> Result<()> {
     println!("Starting the {} to the UI ...", Paint::blue("port forward"));
 
-    // SAFETY: unwrap is safe because it has a default value.
-    let kfp_only = args.is_present("kfp_only");
-    let port = args.value_of("port").unwrap();
-    let address = args.value_of("address").unwrap();
+    // Command line values take precedence over the config files.
+    let kfp_only = switch(args, "kfp_only", config.ui.kfp_only);
+    let port = args.value_of("port").map(str::to_owned).unwrap_or_else(|| config.ui.port.to_string());
+    let address = args.value_of("address").unwrap_or(&config.ui.address);
     
     PortForward::default()
         .with_kfp_only(kfp_only)
-        .with_port(port)
+        .with_port(&port)
         .with_address(address)
         .ensure(confirm).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+async fn bundle(config: &Config, args: &ArgMatches<'_>) -> Result<()> {
+    let (sub_name, sub_args_option) = args.subcommand();
+
+    match sub_name {
+        "create" => {
+            let sub_args = sub_args_option.unwrap();
+            // SAFETY: unwrap is safe because `path` is required.
+            let path = sub_args.value_of("path").unwrap();
+
+            println!("Creating the offline {} at `{}` ...", Paint::blue("bundle"), path);
+
+            let versions = &config.versions;
+            let checksums = &config.checksums;
+            let service = &config.service;
+            let kfp_only = switch(sub_args, "kfp_only", service.kfp_only);
+
+            if !kfp_only {
+                kf_service::check_components(&service.kf_manifests_version, &service.components)?;
+            }
+
+            let staging = tempfile::tempdir()?;
+            let dir = staging.path();
+
+            K3d::default().with_version(&versions.k3d).with_sha256(checksums.k3d.get(&versions.k3d)).bundle(dir).await?;
+            Kubectl::default().with_version(&versions.kubectl).with_sha256(checksums.kubectl.get(&versions.kubectl)).bundle(dir).await?;
+
+            let kfctl = Kfctl::default().with_version(&versions.kfctl).with_sha256(checksums.kfctl.get(&versions.kfctl));
+            if kfctl.is_released() {
+                kfctl.bundle(dir).await?;
+            } else {
+                warn_kfctl_not_released();
+            }
+
+            Kustomize::default().with_version(&versions.kustomize).with_sha256(checksums.kustomize.get(&versions.kustomize)).bundle(dir).await?;
+            K9s::default().with_version(&versions.k9s).with_sha256(checksums.k9s.get(&versions.k9s)).bundle(dir).await?;
+            Kfp::default().with_version(versions.kfp_sdk(&service.kfp_version)).with_server_version(&service.kfp_version).bundle(dir).await?;
+            KfpService::default().with_kfp_version(&service.kfp_version).bundle(dir).await?;
+
+            // KubeFlow is rendered with the bundled `kustomize` (whatever the configured installer, since kfctl needs the
+            // internet to deploy).
+            if !kfp_only {
+                KfService::default()
+                    .with_installer(KfInstaller::Manifests)
+                    .with_manifests_version(&service.kf_manifests_version)
+                    .with_components(&service.components)
+                    .bundle(dir).await?;
+            }
+
+            // The images are saved last, since they are read out of the rendered manifests.
+            K3dService::default().with_k3d_image(&service.k3d_image).bundle(dir).await?;
+
+            if command::is_dry_run() {
+                return Ok(());
+            }
+
+            let manifest = Manifest {
+                k3d_image: service.k3d_image.clone(),
+                kfp_version: service.kfp_version.clone(),
+                kf_manifests_version: if kfp_only { None } else { Some(service.kf_manifests_version.clone()) },
+                components: if kfp_only { Vec::new() } else { service.components.clone() },
+                images: bundle::manifest_images(dir)?,
+                versions: versions.clone(),
+            };
+
+            bundle::create(dir, &manifest, path)?;
+
+            println!("Successfully created the bundle (install from it with `kfpl --bundle {} init`).", path);
+        },
+        _ => return Err(Error::msg("Please use a subcommand (check out `kfpl bundle -h` for help)."))
+    }
+
+    Ok(())
+}
+
+fn profile(config: &Config, args: &ArgMatches<'_>) -> Result<()> {
+    match args.subcommand_name() {
+        Some("list") => {
+            if config.profiles.is_empty() {
+                println!("No {} are defined in the config files.", Paint::blue("profiles"));
+                return Ok(());
+            }
+
+            println!("Listing the {} defined in the config files ...", Paint::blue("profiles"));
+
+            for (name, profile) in &config.profiles {
+                let active = if config.profile.as_deref() == Some(name) { format!(" ({})", Paint::green("active")) } else { String::new() };
+                let description = profile.description.as_deref().map(|d| format!(": {}", d)).unwrap_or_default();
+
+                let service = &profile.config.service;
+                let deployment = if service.kfp_only {
+                    format!("KFP {}", service.kfp_version)
+                } else if !service.components.is_empty() {
+                    format!("KF ({})", service.components.join(", "))
+                } else {
+                    "KF".to_owned()
+                };
+                let ui = &profile.config.ui;
+
+                println!("  `{}`{}{}", Paint::blue(name), active, description);
+                println!("    cluster `{}` ({}) with {}, UI on {}:{}", service.k3d_cluster_name, service.k3d_image, deployment, ui.address, ui.port);
+            }
+
+            Ok(())
+        },
+        _ => Err(Error::msg("Please use a subcommand (check out `kfpl profile -h` for help)."))
+    }
+}
+
+async fn status(config: &Config, args: &ArgMatches<'_>) -> Result<()> {
+    let service = &config.service;
+    let ui = &config.ui;
+    let prefix = &config.install.prefix;
+
+    let mut statuses = vec![
+        Git::default().status().await,
+        K3d::default().with_prefix(prefix).status().await,
+        Kubectl::default().with_prefix(prefix).status().await,
+        match service.kf_installer {
+            KfInstaller::Manifests => Kustomize::default().with_prefix(prefix).status().await,
+            KfInstaller::Kfctl => Kfctl::default().with_prefix(prefix).status().await,
+        },
+        K9s::default().with_prefix(prefix).status().await,
+        Pip3::default().status().await,
+        Kfp::default().status().await,
+        Docker::default().status().await,
+        cluster_provider(service.provider, K3dService::default(), &service.k3d_cluster_name).status().await,
+    ];
+
+    if service.kfp_only {
+        statuses.push(KfpService::default()
+            .with_kfp_version(&service.kfp_version)
+            .with_k3d_cluster_name(&service.k3d_cluster_name)
+            .status().await);
+    } else {
+        statuses.push(KfService::default()
+            .with_installer(service.kf_installer)
+            .with_manifests_version(&service.kf_manifests_version)
+            .with_components(&service.components)
+            .with_kf_yaml(&service.kf_yaml)
+            .with_k3d_cluster_name(&service.k3d_cluster_name)
+            .status().await);
+    }
+
+    if let Some(port) = service.expose_port {
+        statuses.push(Ingress::default()
+            .with_kfp_only(service.kfp_only)
+            .with_port(port)
+            .with_k3d_cluster_name(&service.k3d_cluster_name)
+            .status().await);
+    }
+
+    let missing = statuses.iter().filter(|s| !s.present).count();
+    let outdated = statuses.iter().filter(|s| s.outdated).count();
+    let required = statuses.len();
+
+    // The port forward only lives as long as `kfpl ui`, so it is reported, but not required.
+    statuses.push(PortForward::default()
+        .with_kfp_only(ui.kfp_only)
+        .with_port(&ui.port.to_string())
+        .with_address(&ui.address)
+        .status().await);
+
+    // SAFETY: unwrap is safe because it has a default value.
+    match args.value_of("output").unwrap() {
+        "json" => println!("{}", serde_json::to_string_pretty(&statuses)?),
+        _ => print_status_table(&statuses)
+    }
+
+    if missing > 0 || outdated > 0 {
+        return Err(anyhow!("{} of the {} entities are not present, and {} are outdated.", missing, required, outdated));
+    }
+
+    Ok(())
+}
+
+/// Reads a flag that the config can turn on, and that its `no_` counterpart turns back off.
+fn switch(args: &ArgMatches<'_>, name: &str, config: bool) -> bool {
+    if args.is_present(format!("no_{}", name)) {
+        false
+    } else {
+        args.is_present(name) || config
+    }
+}
+
+/// Picks the cluster of the provider, named `name` (the k3d options only apply to k3d).
+fn cluster_provider(provider: Provider, k3d: K3dService, name: &str) -> Box<dyn ClusterProvider + Send + Sync> {
+    match provider {
+        Provider::K3d => Box::new(k3d.with_k3d_cluster_name(name)),
+        Provider::Kind => Box::new(KindService::default().with_cluster_name(name)),
+        Provider::Minikube => Box::new(MinikubeService::default().with_cluster_name(name)),
+    }
+}
+
+/// Makes the cluster present, and points `kubectl` at it (the deployments go into the current context).
+async fn ensure_cluster(cluster: &(dyn ClusterProvider + Send + Sync), confirm: bool) -> Result<()> {
+    cluster.ensure(confirm).await?;
+
+    // A skipped cluster has no kubeconfig to export.
+    if command::is_dry_run() || cluster.is_present().await? {
+        cluster.export_kubeconfig().await?;
+    }
+
+    Ok(())
+}
+
+/// Removes the cluster, and returns whether it is gone.
+async fn remove_cluster(cluster: &(dyn ClusterProvider + Send + Sync), confirm: bool) -> Result<bool> {
+    cluster.remove(confirm).await?;
+
+    Ok(!command::is_dry_run() && !cluster.is_present().await?)
+}
+
+/// Picks the kfp SDK for a KFP backend, and warns when the config pins one that differs.
+fn kfp_sdk<'a>(versions: &'a VersionsConfig, kfp_version: &'a str) -> &'a str {
+    let sdk = versions.kfp_sdk(kfp_version);
+
+    if sdk != kfp_version {
+        println!("{}", Paint::yellow(format!("The kfp SDK is pinned to {} [config: `versions.kfp`], which differs from KFP {} (expect API errors).", sdk, kfp_version)));
+    }
+
+    sdk
+}
+
+fn print_registry_usage(registry: &Registry) {
+    println!("Push images to `{}/<image>`, and use `{}/<image>` in the pipelines.", Paint::blue(registry.push_address()), Paint::blue(registry.cluster_address()));
+}
+
+fn warn_kfctl_not_released() {
+    println!("{}", Paint::yellow(format!("Skipping kfctl, which is only released for amd64 (rather than {}), so KF can only be deployed from the manifests (with `--kf-installer manifests`).", Arch::current())));
+}
+
+fn warn_if_not_on_path(prefix: &str) {
+    if !helpers::is_on_path(prefix) {
+        println!("{}", Paint::yellow(format!("The install prefix `{}` is not on your `PATH` (add it with `export PATH=\"{}:$PATH\"`).", prefix, prefix)));
+    }
+}
+
+fn print_status_table(statuses: &[Status]) {
+    println!("{:<16}{:<10}{:<36}{:<16}READY", "ENTITY", "PRESENT", "VERSION", "INSTALLED BY");
+
+    for status in statuses {
+        let present = if status.present { Paint::green("✔ yes") } else { Paint::red("✘ no ") };
+        let readiness = status.readiness.as_ref().map(|r| format!("{}/{} {}", r.ready, r.total, r.kind)).unwrap_or_else(|| "-".to_owned());
+
+        let mut version = status.version.clone().unwrap_or_else(|| "-".to_owned());
+        if let (true, Some(minimum)) = (status.outdated, &status.minimum_version) {
+            version = format!("{} (outdated, < {})", version, minimum);
+        }
+
+        let installed_by = match &status.install {
+            Some(install) => format!("kfpl ({})", install.version.as_deref().unwrap_or("-")),
+            None => "-".to_owned()
+        };
+
+        println!("{:<16}{:<10}{:<36}{:<16}{}", status.name, present, version, installed_by, readiness);
+    }
+}
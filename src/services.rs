@@ -6,9 +6,22 @@ pub mod pip3;
 pub mod kfp;
 pub mod kfp_service;
 pub mod port_forward;
+pub mod ingress;
+pub mod registry;
 pub mod k3d_service;
-pub mod curl;
+pub mod kind_service;
+pub mod minikube_service;
+pub mod provider;
 pub mod git;
 pub mod kf_service;
+pub mod kf_installer;
 pub mod kfctl;
-pub mod k9s;
\ No newline at end of file
+pub mod k9s;
+pub mod kustomize;
+pub mod command;
+pub mod version;
+pub mod checksum;
+pub mod download;
+pub mod rollout;
+pub mod kube;
+pub mod platform;
\ No newline at end of file
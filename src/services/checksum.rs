@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use anyhow::{Result, anyhow};
+use yansi::Paint;
+
+use crate::services::platform::Arch;
+
+static TAB: &str = "  ";
+static REQUIRED: AtomicBool = AtomicBool::new(false);
+
+/// The SHA-256 checksums of the built-in pins, as (`name`, `version`, `arch`, `sha256`), where `arch` is `None` for
+/// architecture-independent downloads.  None are listed yet, so every download is verified against the
+/// `[checksums.<name>]` config instead (or goes unverified, with a warning, unless checksums are required).
+static KNOWN: &[(&str, &str, Option<Arch>, &str)] = &[];
+
+/// Makes a download without a known checksum an error, rather than a warning.
+pub fn set_required(required: bool) {
+    REQUIRED.store(required, Ordering::SeqCst);
+}
+
+pub fn is_required() -> bool {
+    REQUIRED.load(Ordering::SeqCst)
+}
+
+/// Returns the built-in checksum of a pinned download, if there is one.
+pub fn known(name: &str, version: &str, arch: Option<Arch>) -> Option<&'static str> {
+    KNOWN.iter()
+        .find(|(n, v, a, _)| *n == name && *v == version && *a == arch)
+        .map(|(_, _, _, sha256)| *sha256)
+}
+
+/// Picks the checksum that a download is verified against (the configured one wins over the built-in one), or
+/// returns `None` when the download may go unverified.
+pub fn expected(name: &str, version: &str, arch: Option<Arch>, configured: Option<&str>, required: bool) -> Result<Option<String>> {
+    let sha256 = match configured.or_else(|| known(name, version, arch)) {
+        Some(s) => s.trim().to_ascii_lowercase(),
+        None if required => return Err(anyhow!("No SHA-256 checksum is known for `{}` {}, so it is not installed (add one under `[checksums.{}]` in the config).", name, version, name)),
+        None => return Ok(None)
+    };
+
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("The SHA-256 checksum `{}` for `{}` {} is not 64 hex digits.", sha256, name, version));
+    }
+
+    Ok(Some(sha256))
+}
+
+/// Picks the checksum that a download is verified against (per `is_required`), and warns when there is none.
+pub(crate) fn resolve(name: &str, version: &str, arch: Option<Arch>, configured: Option<&str>) -> Result<Option<String>> {
+    let sha256 = expected(name, version, arch, configured, is_required())?;
+
+    if sha256.is_none() {
+        println!("{}{}", TAB, Paint::yellow(format!("No SHA-256 checksum is known for `{}` {}, so the download is not verified.", name, version)));
+    }
+
+    Ok(sha256)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SHA256: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn expected_prefers_the_configured_checksum() {
+        let configured = SHA256.replace('0', "A");
+
+        assert_eq!(expected("kubectl", "v1.19.2", Some(Arch::Amd64), Some(&configured), true).unwrap(), Some(SHA256.replace('0', "a")));
+    }
+
+    #[test]
+    fn expected_refuses_unknown_checksums_only_when_required() {
+        assert_eq!(expected("kubectl", "v0.0.0", Some(Arch::Amd64), None, false).unwrap(), None);
+        assert!(expected("kubectl", "v0.0.0", Some(Arch::Amd64), None, true).is_err());
+    }
+
+    #[test]
+    fn expected_rejects_malformed_checksums() {
+        let err = expected("kubectl", "v1.19.2", Some(Arch::Amd64), Some("abc; rm -rf /"), false).unwrap_err();
+
+        assert_eq!(err.to_string(), "The SHA-256 checksum `abc; rm -rf /` for `kubectl` v1.19.2 is not 64 hex digits.");
+    }
+}
@@ -0,0 +1,379 @@
+use std::{fmt, fs, ops::Deref, path::Path, process::Stdio, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+use tokio::{process::Command, time::{delay_for, Duration}};
+use anyhow::{Result, Context};
+use async_trait::async_trait;
+use yansi::Paint;
+
+use crate::{helpers::ExitStatusIntoUnit, services::{download::{self, Download}, kube::ApiGet}};
+
+static TAB: &str = "  ";
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Switches every subsequent step into "plan" mode, where steps are printed rather than executed.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::SeqCst);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// The captured result of a read-only query.
+#[derive(Clone, Debug, Default)]
+pub struct Output {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Runners.
+
+/// Executes the commands described by the entities.
+#[async_trait]
+pub trait CommandRunner: Send + Sync {
+    /// Executes a step with inherited stdio (so the user may interact with it).
+    async fn run(&self, cmd: &Cmd) -> Result<()>;
+    /// Executes a read-only query, and captures its output.
+    async fn query(&self, cmd: &Cmd) -> Result<Output>;
+    /// Fetches, verifies, and installs a file (in-process).
+    async fn download(&self, download: &Download) -> Result<()>;
+    /// Makes a read-only request to the Kubernetes API (in-process), and captures the response.
+    async fn get(&self, request: &ApiGet) -> Result<Output>;
+    /// Reads a (small) text file from a URL or a path (in-process), and captures it.
+    async fn read(&self, url: &str) -> Result<Output>;
+    /// Writes a (small) text file (in-process).
+    async fn write(&self, path: &Path, contents: &str) -> Result<()>;
+    /// Pauses between steps.
+    async fn wait(&self, duration: Duration);
+}
+
+/// A shared handle to a `CommandRunner`, which defaults to spawning real processes.
+#[derive(Clone)]
+pub struct Runner(Arc<dyn CommandRunner>);
+
+impl Runner {
+    pub fn new<R: CommandRunner + 'static>(runner: R) -> Self {
+        Self(Arc::new(runner))
+    }
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new(ProcessRunner)
+    }
+}
+
+impl Deref for Runner {
+    type Target = dyn CommandRunner;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// Spawns real processes (or, during a dry run, only prints the steps, while still executing queries).
+pub struct ProcessRunner;
+
+#[async_trait]
+impl CommandRunner for ProcessRunner {
+    async fn run(&self, cmd: &Cmd) -> Result<()> {
+        if is_dry_run() {
+            println!("{}{}{} {}", TAB, TAB, Paint::yellow("would run:"), cmd);
+            return Ok(());
+        }
+
+        Command::new(&cmd.program)
+            .args(&cmd.args)
+            .status().await
+            .status_to_unit()
+    }
+
+    async fn query(&self, cmd: &Cmd) -> Result<Output> {
+        let output = Command::new(&cmd.program)
+            .args(&cmd.args)
+            .stdin(Stdio::null())
+            .output().await?;
+
+        Ok(Output {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    async fn download(&self, download: &Download) -> Result<()> {
+        if is_dry_run() {
+            println!("{}{}{} {}", TAB, TAB, Paint::yellow("would run:"), download);
+            return Ok(());
+        }
+
+        let download = download.clone();
+
+        tokio::task::spawn_blocking(move || download.install()).await?
+    }
+
+    async fn get(&self, request: &ApiGet) -> Result<Output> {
+        let request = request.clone();
+
+        tokio::task::spawn_blocking(move || request.fetch()).await?
+    }
+
+    async fn read(&self, url: &str) -> Result<Output> {
+        let url = url.to_owned();
+
+        // An unreadable file is an unsuccessful output, as a failed query would be.
+        Ok(match tokio::task::spawn_blocking(move || download::read(&url)).await? {
+            Ok(text) => Output { success: true, stdout: text, stderr: String::new() },
+            Err(e) => Output { success: false, stdout: String::new(), stderr: format!("{:#}", e) }
+        })
+    }
+
+    async fn write(&self, path: &Path, contents: &str) -> Result<()> {
+        if is_dry_run() {
+            println!("{}{}{} {}", TAB, TAB, Paint::yellow("would write:"), path.display());
+            return Ok(());
+        }
+
+        let (path, contents) = (path.to_owned(), contents.to_owned());
+
+        tokio::task::spawn_blocking(move || fs::write(&path, contents).with_context(|| format!("Unable to write `{}`.", path.display()))).await?
+    }
+
+    async fn wait(&self, duration: Duration) {
+        if is_dry_run() {
+            println!("{}{}{} {}s", TAB, TAB, Paint::yellow("would wait:"), duration.as_secs());
+            return;
+        }
+
+        delay_for(duration).await;
+    }
+}
+
+// Commands.
+
+/// A description of an external command, which is either executed as a step, or as a read-only query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cmd {
+    program: String,
+    args: Vec<String>,
+    context: String,
+}
+
+impl Cmd {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_owned(),
+            args: Vec::new(),
+            context: format!("Unable to run `{}`.", program),
+        }
+    }
+
+    pub fn arg<S: Into<String>>(mut self, a: S) -> Self {
+        self.args.push(a.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+        where I: IntoIterator<Item = S>, S: Into<String>
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets the error message reported when the step fails.
+    pub fn context(mut self, c: &str) -> Self {
+        self.context = c.to_owned();
+        self
+    }
+
+    /// Executes the command as a step.
+    pub async fn run(self, runner: &Runner) -> Result<()> {
+        runner.run(&self).await.context(self.context)
+    }
+
+    /// Executes the command as a read-only query (even during a dry run), and captures its output.
+    pub async fn output(self, runner: &Runner) -> Result<Output> {
+        runner.query(&self).await.context(self.context)
+    }
+
+    /// Executes the command as a read-only query (even during a dry run), and returns whether it succeeded.
+    pub async fn succeeds(self, runner: &Runner) -> Result<bool> {
+        Ok(self.output(runner).await?.success)
+    }
+}
+
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+
+        for arg in &self.args {
+            write!(f, " {}", quote(arg))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Quotes a word for display, so that printed plans can be pasted into a shell.
+pub(crate) fn quote(word: &str) -> String {
+    if word.is_empty() || word.contains(|c: char| c.is_whitespace() || "\"'$;&|<>(){}*?\\`".contains(c)) {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    } else {
+        word.to_owned()
+    }
+}
+
+// Test helpers.
+
+#[cfg(test)]
+pub mod fake {
+    use std::sync::Mutex;
+    use anyhow::anyhow;
+
+    use super::*;
+
+    /// Records every command (steps, downloads, writes, queries, API requests, and reads, in order) instead of executing it.
+    /// Queries succeed with a canned output when they start with a registered prefix (and fail otherwise), and steps
+    /// succeed unless they start with a registered failure prefix.
+    #[derive(Default)]
+    pub struct RecordingRunner {
+        commands: Mutex<Vec<String>>,
+        responses: Vec<(Option<String>, String, String)>,
+        failures: Vec<String>,
+        query_failures: Mutex<Vec<(String, String, usize)>>,
+    }
+
+    impl RecordingRunner {
+        pub fn respond(mut self, prefix: &str, stdout: &str) -> Self {
+            self.responses.push((None, prefix.to_owned(), stdout.to_owned()));
+            self
+        }
+
+        /// Responds only once a step that starts with `after` was recorded (e.g., a deployment that only shows up
+        /// once it was applied).
+        pub fn respond_after(mut self, after: &str, prefix: &str, stdout: &str) -> Self {
+            self.responses.push((Some(after.to_owned()), prefix.to_owned(), stdout.to_owned()));
+            self
+        }
+
+        pub fn fail(mut self, prefix: &str) -> Self {
+            self.failures.push(prefix.to_owned());
+            self
+        }
+
+        /// Fails the first `times` queries that start with the prefix (with the stderr), after which they fall
+        /// through to the responses.
+        pub fn fail_query(self, prefix: &str, stderr: &str, times: usize) -> Self {
+            self.query_failures.lock().unwrap().push((prefix.to_owned(), stderr.to_owned(), times));
+            self
+        }
+
+        pub fn into_runner(self) -> (Runner, Arc<RecordingRunner>) {
+            let recorder = Arc::new(self);
+
+            (Runner(recorder.clone()), recorder)
+        }
+
+        pub fn commands(&self) -> Vec<String> {
+            self.commands.lock().unwrap().clone()
+        }
+
+        fn record<D: fmt::Display>(&self, step: &D) -> String {
+            let line = step.to_string();
+            self.commands.lock().unwrap().push(line.clone());
+            line
+        }
+
+        fn respond_to(&self, line: &str) -> Output {
+            if let Some((_, stderr, times)) = self.query_failures.lock().unwrap().iter_mut().find(|(prefix, _, times)| line.starts_with(prefix.as_str()) && *times > 0) {
+                *times -= 1;
+                return Output { success: false, stdout: String::new(), stderr: stderr.clone() };
+            }
+
+            let commands = self.commands();
+            let is_due = |after: &Option<String>| after.as_ref().map(|a| commands.iter().any(|c| c.starts_with(a))).unwrap_or(true);
+
+            self.responses.iter()
+                .find(|(after, prefix, _)| line.starts_with(prefix) && is_due(after))
+                .map(|(_, _, stdout)| Output { success: true, stdout: stdout.clone(), stderr: String::new() })
+                .unwrap_or_default()
+        }
+    }
+
+    #[async_trait]
+    impl CommandRunner for RecordingRunner {
+        async fn run(&self, cmd: &Cmd) -> Result<()> {
+            let line = self.record(cmd);
+
+            if self.failures.iter().any(|f| line.starts_with(f)) {
+                return Err(anyhow!("The exit code of the operation was not successful."));
+            }
+
+            Ok(())
+        }
+
+        async fn query(&self, cmd: &Cmd) -> Result<Output> {
+            let line = self.record(cmd);
+
+            Ok(self.respond_to(&line))
+        }
+
+        async fn get(&self, request: &ApiGet) -> Result<Output> {
+            let line = self.record(request);
+
+            Ok(self.respond_to(&line))
+        }
+
+        async fn read(&self, url: &str) -> Result<Output> {
+            let line = self.record(&format!("read {}", url));
+
+            Ok(self.respond_to(&line))
+        }
+
+        async fn write(&self, path: &Path, contents: &str) -> Result<()> {
+            let line = self.record(&format!("write {} | {}", path.display(), contents));
+
+            if self.failures.iter().any(|f| line.starts_with(f)) {
+                return Err(anyhow!("The write was not successful."));
+            }
+
+            Ok(())
+        }
+
+        async fn download(&self, download: &Download) -> Result<()> {
+            let line = self.record(download);
+
+            if self.failures.iter().any(|f| line.starts_with(f)) {
+                return Err(anyhow!("The download was not successful."));
+            }
+
+            Ok(())
+        }
+
+        async fn wait(&self, _duration: Duration) {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_quotes_shell_sensitive_arguments() {
+        let cmd = Cmd::new("sh")
+            .arg("-c")
+            .arg("cp $HOME/.local/bin/kfp /usr/local/bin/kfp")
+            .arg("it's");
+
+        assert_eq!(cmd.to_string(), r"sh -c 'cp $HOME/.local/bin/kfp /usr/local/bin/kfp' 'it'\''s'");
+    }
+
+    #[tokio::test]
+    async fn run_attaches_the_step_context() {
+        let (runner, _) = fake::RecordingRunner::default().fail("k3d").into_runner();
+
+        let err = Cmd::new("k3d").context("Unable to start.").run(&runner).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to start.");
+    }
+}
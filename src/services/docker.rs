@@ -1,16 +1,61 @@
-use tokio::process::Command;
 use anyhow::{Result, Context};
 use async_trait::async_trait;
 
 use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
+    services::model::{Nameable, Ensurable, Removable, Inspectable, Trackable, is_binary_present, binary_version},
+    services::version::Version,
+    services::command::{Cmd, Runner},
+    services::checksum,
+    services::download::Download,
+    services::platform::{Distro, PackageManager}
 };
 
 static NAME: &str = "docker";
+static MINIMUM_VERSION: &str = "v19.3.0";
+static SCRIPT_VERSION: &str = "install script";
+static SCRIPT_FILE: &str = "kfpl-get-docker.sh";
 
-#[derive(Default)]
-pub struct Docker {}
+pub struct Docker {
+    script_sha256: Option<String>,
+    distro: Distro,
+    runner: Runner
+}
+
+impl Default for Docker {
+    fn default() -> Self {
+        Self {
+            script_sha256: None,
+            distro: Distro::current(),
+            runner: Runner::default()
+        }
+    }
+}
+
+impl Docker {
+    /// Sets the checksum that the (unversioned) convenience script is verified against.
+    pub fn with_script_sha256(mut self, s: Option<&str>) -> Self {
+        self.script_sha256 = s.map(str::to_owned);
+        self
+    }
+
+    /// Installs docker with the (verified) convenience script, out of a private temp directory (which is removed
+    /// when it goes out of scope, even if the script fails).
+    async fn run_script(&self) -> Result<()> {
+        let dir = tempfile::tempdir().context("Unable to create a temp directory for the docker install script.")?;
+        let script = dir.path().join(SCRIPT_FILE).to_string_lossy().into_owned();
+
+        Download::new("https://get.docker.com")
+            .sha256(checksum::resolve(NAME, SCRIPT_VERSION, None, self.script_sha256.as_deref())?)
+            .to(&script)
+            .context("Unable to download the docker convenience script.")
+            .run(&self.runner).await?;
+
+        Cmd::new("sh")
+            .arg(&script)
+            .context("Unable to run the docker install script (might need sudo).")
+            .run(&self.runner).await
+    }
+}
 
 impl Nameable for Docker {
     fn name(&self) -> &'static str {
@@ -21,40 +66,57 @@ impl Nameable for Docker {
 #[async_trait]
 impl Ensurable for Docker {
     async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
+        is_binary_present(self, &self.runner).await
     }
 
     async fn make_present(&self) -> Result<()> {
-        Command::new("curl")
-            .arg("-fsSL")
-            .arg("https://get.docker.com")
-            .arg("-o")
-            .arg("get-docker.sh")
-            .status().await
-            .status_to_unit()
-            .context("Unable to curl the docker convenience script.")?;
-
-        Command::new("sh")
-            .arg("get-docker.sh")
-            .status().await
-            .status_to_unit()
-            .context("Unable to run the docker install script (might need sudo).")?;
+        match self.distro.package_manager()? {
+            // The convenience script does not cover these distributions, which package docker themselves.
+            manager @ (PackageManager::Apk | PackageManager::Pacman) => {
+                for cmd in manager.install(&["docker"]) {
+                    cmd
+                        .context(&format!("Unable to install docker via {} (might need sudo).", manager))
+                        .run(&self.runner).await?;
+                }
+            },
+            _ => self.run_script().await?
+        }
 
         // Ignore failure: might be in container.
-        Command::new("usermod")
+        Cmd::new("usermod")
             .arg("-aG")
             .arg("docker")
             .arg("$USER")
-            .status().await
-            .status_to_unit().unwrap_or(());
-                
-        Command::new("rm")
-            .arg("-f")
-            .arg("get-docker.sh")
-            .status().await
-            .status_to_unit()
-            .context("Failed to delete the docker install script.")?;
+            .run(&self.runner).await.unwrap_or(());
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[async_trait]
+impl Inspectable for Docker {
+    async fn version(&self) -> Result<Option<String>> {
+        binary_version(self, &self.runner, &["--version"]).await
+    }
+
+    fn minimum_version(&self) -> Option<Version> {
+        Version::parse(MINIMUM_VERSION)
+    }
+}
+
+#[async_trait]
+impl Removable for Docker {
+    async fn make_not_present(&self) -> Result<()> {
+        let manager = self.distro.package_manager()?;
+        let packages: &[&str] = match manager {
+            PackageManager::Apk | PackageManager::Pacman => &["docker"],
+            _ => &["docker-ce", "docker-ce-cli", "containerd.io"]
+        };
+
+        manager.remove(packages)
+            .context(&format!("Unable to remove docker via {} (might need sudo).", manager))
+            .run(&self.runner).await
+    }
+}
+
+impl Trackable for Docker {}
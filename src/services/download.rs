@@ -0,0 +1,435 @@
+use std::{fmt, fs::{self, File, Permissions}, io, marker::PhantomData, os::unix::fs::PermissionsExt, path::{Path, PathBuf}};
+use anyhow::{Result, Context, anyhow};
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use tar::Archive;
+
+use crate::{
+    services::model::{Nameable, Ensurable, Removable, Inspectable, Upgradable, Trackable, Bundleable},
+    services::version::{Version, parse_version},
+    services::command::{Cmd, Runner},
+    services::checksum,
+    services::platform::Arch,
+    bundle::{self, BIN_DIR},
+    helpers::SYSTEM_PREFIX
+};
+
+static INSTALL_MODE: u32 = 0o755;
+
+/// A description of a file to fetch, verify, (optionally) extract from a `.tar.gz`, and install, which happens
+/// in-process (rather than via `curl`, `tar`, `chmod`, and `mv`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Download {
+    url: String,
+    sha256: Option<String>,
+    member: Option<String>,
+    destination: String,
+    context: String,
+}
+
+impl Download {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            sha256: None,
+            member: None,
+            destination: String::new(),
+            context: format!("Unable to download `{}`.", url),
+        }
+    }
+
+    /// Sets the checksum that the download must match (if any).
+    pub fn sha256(mut self, s: Option<String>) -> Self {
+        self.sha256 = s;
+        self
+    }
+
+    /// Installs a single file out of the downloaded `.tar.gz` (rather than the download itself).
+    pub fn extract(mut self, member: &str) -> Self {
+        self.member = Some(member.to_owned());
+        self
+    }
+
+    /// Sets where the (executable) file is installed.
+    pub fn to(mut self, destination: &str) -> Self {
+        self.destination = destination.to_owned();
+        self
+    }
+
+    /// Sets the error message reported when the download fails.
+    pub fn context(mut self, c: &str) -> Self {
+        self.context = c.to_owned();
+        self
+    }
+
+    /// Executes the download as a step.
+    pub async fn run(self, runner: &Runner) -> Result<()> {
+        runner.download(&self).await.context(self.context)
+    }
+
+    /// Fetches, verifies, extracts, and installs the file (blocking), within a temp directory that is always removed.
+    pub(crate) fn install(&self) -> Result<()> {
+        let temp_dir = tempfile::tempdir().context("Unable to create a temp directory.")?;
+        let fetched = temp_dir.path().join("download");
+
+        fetch(&self.url, &fetched)?;
+
+        if let Some(expected) = &self.sha256 {
+            verify(&fetched, expected, &self.url)?;
+        }
+
+        let payload = match &self.member {
+            Some(member) => extract(&fetched, member, temp_dir.path())?,
+            None => fetched
+        };
+
+        install(&payload, Path::new(&self.destination))
+    }
+}
+
+impl fmt::Display for Download {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "download {}", self.url)?;
+
+        if let Some(sha256) = &self.sha256 {
+            write!(f, " | verify sha256 {}", sha256)?;
+        }
+
+        if let Some(member) = &self.member {
+            write!(f, " | extract {}", member)?;
+        }
+
+        write!(f, " | install {}", self.destination)
+    }
+}
+
+// Release binaries.
+
+/// What differs between the tools that are installed as a single binary out of a release (the names, the pins, and
+/// where the release asset is), since `ReleaseBinary` does the rest.
+pub trait Release: Send + Sync + 'static {
+    fn name() -> &'static str;
+
+    /// The version that is installed unless another one is pinned.
+    fn pin() -> &'static str;
+
+    /// The oldest version that kfpl works with.
+    fn minimum_version() -> &'static str;
+
+    /// The arguments that make the binary print its version.
+    fn version_args() -> &'static [&'static str];
+
+    /// The release asset of a version, or an error when there is none for the architecture.
+    fn url(version: &str, arch: Arch) -> Result<String>;
+
+    /// The file to install out of the release asset, when the asset is a `.tar.gz` (rather than the binary itself).
+    fn member() -> Option<&'static str> {
+        None
+    }
+}
+
+/// A tool that is installed into the prefix as a single binary, out of its pinned (and verified) release, or out of
+/// an offline bundle.  (The fields are visible to the tool modules, so that their tests can set the runner.)
+pub struct ReleaseBinary<R> {
+    pub(super) version: String,
+    pub(super) sha256: Option<String>,
+    pub(super) bundle_dir: Option<PathBuf>,
+    pub(super) prefix: String,
+    pub(super) arch: Arch,
+    pub(super) runner: Runner,
+    pub(super) release: PhantomData<R>,
+}
+
+impl<R: Release> Default for ReleaseBinary<R> {
+    fn default() -> Self {
+        Self {
+            version: R::pin().to_owned(),
+            sha256: None,
+            bundle_dir: None,
+            prefix: SYSTEM_PREFIX.to_owned(),
+            arch: Arch::current(),
+            runner: Runner::default(),
+            release: PhantomData,
+        }
+    }
+}
+
+impl<R: Release> ReleaseBinary<R> {
+    pub fn with_version(mut self, v: &str) -> Self {
+        self.version = v.to_owned();
+        self
+    }
+
+    /// Overrides the checksum that the download is verified against (otherwise, the built-in one for the pin is used,
+    /// if there is one).
+    pub fn with_sha256(mut self, s: Option<&str>) -> Self {
+        self.sha256 = s.map(str::to_owned);
+        self
+    }
+
+    /// Sets the directory that the binary is installed into.
+    pub fn with_prefix(mut self, p: &str) -> Self {
+        self.prefix = p.to_owned();
+        self
+    }
+
+    /// Installs the binary out of an (unpacked) offline bundle, rather than downloading it.
+    pub fn with_bundle(mut self, b: Option<&Path>) -> Self {
+        self.bundle_dir = b.map(Path::to_owned);
+        self
+    }
+
+    /// Returns whether the tool is released for this architecture.
+    pub fn is_released(&self) -> bool {
+        self.arch.supported().and_then(|arch| R::url(&self.version, arch)).is_ok()
+    }
+
+    fn install_path(&self) -> String {
+        format!("{}/{}", self.prefix.trim_end_matches('/'), R::name())
+    }
+
+    /// The pinned release (verified against its checksum).
+    fn release_download(&self) -> Result<Download> {
+        let arch = self.arch.supported()?;
+        let download = Download::new(&R::url(&self.version, arch)?)
+            .sha256(checksum::resolve(R::name(), &self.version, Some(arch), self.sha256.as_deref())?);
+
+        Ok(match R::member() {
+            Some(member) => download.extract(member),
+            None => download
+        })
+    }
+
+    /// The pinned release, or the binary out of the bundle (which was verified when the bundle was created).
+    fn download(&self) -> Result<Download> {
+        match &self.bundle_dir {
+            Some(dir) => Ok(Download::new(&format!("file://{}", bundle::file(dir, BIN_DIR, R::name()).display()))),
+            None => self.release_download()
+        }
+    }
+}
+
+impl<R: Release> Nameable for ReleaseBinary<R> {
+    fn name(&self) -> &'static str {
+        R::name()
+    }
+}
+
+#[async_trait]
+impl<R: Release> Ensurable for ReleaseBinary<R> {
+    /// The binary counts as present when the prefix has it, since the prefix may not be on the `PATH` (and another
+    /// install of the tool may be).
+    async fn is_present(&self) -> Result<bool> {
+        Cmd::new("test")
+            .arg("-x")
+            .arg(self.install_path())
+            .succeeds(&self.runner).await
+    }
+
+    async fn make_present(&self) -> Result<()> {
+        self.download()?
+            .to(&self.install_path())
+            .context(&format!("Unable to install the {} binary (might need sudo).", R::name()))
+            .run(&self.runner).await?;
+
+        // The prefix may not be on the `PATH` (yet).
+        Cmd::new(&self.install_path())
+            .args(R::version_args().iter().copied())
+            .context(&format!("Unable to use {} after supposed install.", R::name()))
+            .run(&self.runner).await
+    }
+}
+
+#[async_trait]
+impl<R: Release> Inspectable for ReleaseBinary<R> {
+    async fn version(&self) -> Result<Option<String>> {
+        let output = Cmd::new(&self.install_path())
+            .args(R::version_args().iter().copied())
+            .output(&self.runner).await?;
+
+        Ok(parse_version(&output.stdout).or_else(|| parse_version(&output.stderr)))
+    }
+
+    fn minimum_version(&self) -> Option<Version> {
+        Version::parse(R::minimum_version())
+    }
+}
+
+impl<R: Release> Upgradable for ReleaseBinary<R> {
+    fn pinned_version(&self) -> Option<Version> {
+        Version::parse(&self.version)
+    }
+}
+
+#[async_trait]
+impl<R: Release> Removable for ReleaseBinary<R> {
+    async fn make_not_present(&self) -> Result<()> {
+        Cmd::new("rm")
+            .arg("-f")
+            .arg(self.install_path())
+            .context(&format!("Unable to remove the {} binary (might need sudo).", R::name()))
+            .run(&self.runner).await
+    }
+}
+
+#[async_trait]
+impl<R: Release> Bundleable for ReleaseBinary<R> {
+    async fn make_bundled(&self, dir: &Path) -> Result<()> {
+        self.release_download()?
+            .to(&bundle::file(dir, BIN_DIR, R::name()).to_string_lossy())
+            .context(&format!("Unable to bundle the {} binary.", R::name()))
+            .run(&self.runner).await
+    }
+}
+
+impl<R: Release> Trackable for ReleaseBinary<R> {
+    fn installed_version(&self) -> Option<String> {
+        Some(self.version.clone())
+    }
+
+    fn installed_paths(&self) -> Vec<String> {
+        vec![self.install_path()]
+    }
+}
+
+/// Reads a (small) text file from a `https://` URL, a `file://` one, or a local path (blocking).
+pub(crate) fn read(url: &str) -> Result<String> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        return ureq::get(url).call().with_context(|| format!("Unable to fetch `{}`.", url))?
+            .into_string().with_context(|| format!("Unable to read `{}`.", url));
+    }
+
+    let source = url.strip_prefix("file://").unwrap_or(url);
+
+    fs::read_to_string(source).with_context(|| format!("Unable to read `{}`.", source))
+}
+
+// Pipeline stages.
+
+/// Fetches a `https://` URL, or copies a `file://` one (e.g., out of an offline bundle).
+fn fetch(url: &str, path: &Path) -> Result<()> {
+    if let Some(source) = url.strip_prefix("file://") {
+        fs::copy(source, path).with_context(|| format!("Unable to copy `{}`.", source))?;
+        return Ok(());
+    }
+
+    let response = ureq::get(url).call().with_context(|| format!("Unable to fetch `{}`.", url))?;
+    let mut file = File::create(path).with_context(|| format!("Unable to create `{}`.", path.display()))?;
+
+    io::copy(&mut response.into_reader(), &mut file).with_context(|| format!("Unable to read `{}`.", url))?;
+
+    Ok(())
+}
+
+fn sha256_of(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut file = File::open(path).with_context(|| format!("Unable to open `{}`.", path.display()))?;
+
+    io::copy(&mut file, &mut hasher).with_context(|| format!("Unable to read `{}`.", path.display()))?;
+
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Refuses (with an error) to go any further when the fetched file does not match its checksum.
+fn verify(path: &Path, expected: &str, url: &str) -> Result<()> {
+    let actual = sha256_of(path)?;
+
+    if actual != expected {
+        return Err(anyhow!("The SHA-256 checksum of `{}` is {}, rather than {}.", url, actual, expected));
+    }
+
+    Ok(())
+}
+
+/// Unpacks the file named `member` (at any depth) out of a `.tar.gz`, into `dir`.
+fn extract(archive: &Path, member: &str, dir: &Path) -> Result<PathBuf> {
+    let file = File::open(archive).with_context(|| format!("Unable to open `{}`.", archive.display()))?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+
+    for entry in archive.entries().context("Unable to read the tarball.")? {
+        let mut entry = entry.context("Unable to read the tarball.")?;
+
+        if entry.path()?.file_name().and_then(|n| n.to_str()) == Some(member) {
+            let path = dir.join(member);
+            entry.unpack(&path).with_context(|| format!("Unable to extract `{}` from the tarball.", member))?;
+
+            return Ok(path);
+        }
+    }
+
+    Err(anyhow!("The tarball does not contain `{}`.", member))
+}
+
+/// Stages the file next to the destination, and then renames it into place, so that the destination is never left
+/// half-written.
+fn install(source: &Path, destination: &Path) -> Result<()> {
+    let file_name = destination.file_name().and_then(|n| n.to_str()).context("The install destination has no file name.")?;
+    let staged = destination.with_file_name(format!(".{}.kfpl-tmp", file_name));
+
+    if let Some(dir) = destination.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Unable to create `{}` (might need sudo).", dir.display()))?;
+    }
+
+    let result = fs::copy(source, &staged)
+        .and_then(|_| fs::set_permissions(&staged, Permissions::from_mode(INSTALL_MODE)))
+        .and_then(|_| fs::rename(&staged, destination));
+
+    if result.is_err() {
+        fs::remove_file(&staged).unwrap_or(());
+    }
+
+    result.with_context(|| format!("Unable to install `{}` (might need sudo).", destination.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn display_describes_every_stage() {
+        let download = Download::new("https://example.com/k9s.tar.gz")
+            .sha256(Some("ab".repeat(32)))
+            .extract("k9s")
+            .to("/usr/local/bin/k9s");
+
+        assert_eq!(download.to_string(), format!("download https://example.com/k9s.tar.gz | verify sha256 {} | extract k9s | install /usr/local/bin/k9s", "ab".repeat(32)));
+    }
+
+    #[test]
+    fn extract_and_install_a_nested_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("k9s.tar.gz");
+
+        let mut builder = tar::Builder::new(GzEncoder::new(File::create(&archive).unwrap(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        builder.append_data(&mut header, "release/k9s", &b"k9s!"[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap().flush().unwrap();
+
+        let extracted = extract(&archive, "k9s", dir.path()).unwrap();
+        let destination = dir.path().join("bin-k9s");
+        install(&extracted, &destination).unwrap();
+
+        assert_eq!(fs::read(&destination).unwrap(), b"k9s!");
+        assert_eq!(fs::metadata(&destination).unwrap().permissions().mode() & 0o777, INSTALL_MODE);
+        assert!(extract(&archive, "LICENSE", dir.path()).is_err());
+    }
+
+    #[test]
+    fn verify_refuses_a_checksum_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty");
+        File::create(&path).unwrap();
+
+        let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let err = verify(&path, &"a".repeat(64), "https://example.com/empty").unwrap_err();
+
+        assert!(verify(&path, empty, "https://example.com/empty").is_ok());
+        assert_eq!(err.to_string(), format!("The SHA-256 checksum of `https://example.com/empty` is {}, rather than {}.", empty, "a".repeat(64)));
+    }
+}
@@ -1,16 +1,27 @@
-use tokio::process::Command;
-use anyhow::{Result, Context};
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 
 use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
+    services::model::{Nameable, Ensurable, Inspectable, Trackable, is_binary_present, binary_version},
+    services::command::{Cmd, Runner},
+    services::platform::Distro
 };
 
 static NAME: &str = "git";
 
-#[derive(Default)]
-pub struct Git {}
+pub struct Git {
+    distro: Distro,
+    runner: Runner
+}
+
+impl Default for Git {
+    fn default() -> Self {
+        Self {
+            distro: Distro::current(),
+            runner: Runner::default()
+        }
+    }
+}
 
 impl Nameable for Git {
     fn name(&self) -> &'static str {
@@ -21,31 +32,33 @@ impl Nameable for Git {
 #[async_trait]
 impl Ensurable for Git {
     async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
+        is_binary_present(self, &self.runner).await
     }
 
     async fn make_present(&self) -> Result<()> {
-        Command::new("apt-get")
-            .arg("update")
-            .status().await
-            .status_to_unit()
-            .context("Unable to update apt-get.")?;
-    
-        Command::new("apt-get")
-            .arg("-y")
-            .arg("install")
-            .arg("git")
-            .status().await
-            .status_to_unit()
-            .context("Unable to install git via apt-get.  You can install git manually, and try again.")?;
+        let manager = self.distro.package_manager()
+            .map_err(|e| anyhow!("{}  You can install git manually, and try again.", e))?;
 
-        Command::new("which")
+        for cmd in manager.install(&["git"]) {
+            cmd
+                .context(&format!("Unable to install git via {}.  You can install git manually, and try again.", manager))
+                .run(&self.runner).await?;
+        }
+
+        Cmd::new("which")
             .arg("git")
-            .status().await
-            .status_to_unit()
-            .context("Unable to verify git installation.")?;
+            .context("Unable to verify git installation.")
+            .run(&self.runner).await?;
 
         Ok(())
     }
 }
 
+#[async_trait]
+impl Inspectable for Git {
+    async fn version(&self) -> Result<Option<String>> {
+        binary_version(self, &self.runner, &["--version"]).await
+    }
+}
+
+impl Trackable for Git {}
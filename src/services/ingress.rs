@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+
+use crate::{
+    services::model::{Nameable, Ensurable, Inspectable, Trackable, verify_present},
+    services::command::{Cmd, Output, Runner, is_dry_run}
+};
+
+static NAME: &str = "UI Ingress";
+static INGRESS_NAME: &str = "kfpl-ui";
+
+/// Routes the k3d load balancer (through traefik) to the UI, so that it outlives the terminal (unlike `kfpl ui`).
+#[derive(Default)]
+pub struct Ingress {
+    kfp_only: bool,
+    port: u16,
+    k3d_cluster_name: String,
+    runner: Runner
+}
+
+impl Ingress {
+    pub fn with_kfp_only(mut self, o: bool) -> Self {
+        self.kfp_only = o;
+        self
+    }
+
+    /// Sets the host port that the load balancer maps to traefik (checked against the cluster, and used for the URL and
+    /// the state).
+    pub fn with_port(mut self, p: u16) -> Self {
+        self.port = p;
+        self
+    }
+
+    /// Names the cluster that the ingress is recorded against.
+    pub fn with_k3d_cluster_name(mut self, n: &str) -> Self {
+        self.k3d_cluster_name = n.to_owned();
+        self
+    }
+
+    /// Returns the namespace and the service that the ingress routes to.
+    fn backend(&self) -> (&'static str, &'static str) {
+        if self.kfp_only {
+            ("kubeflow", "ml-pipeline-ui")
+        } else {
+            ("istio-system", "istio-ingressgateway")
+        }
+    }
+
+    fn manifest(&self) -> String {
+        let (namespace, service) = self.backend();
+
+        format!(r#"apiVersion: networking.k8s.io/v1
+kind: Ingress
+metadata:
+  name: {}
+  namespace: {}
+  annotations:
+    kubernetes.io/ingress.class: traefik
+spec:
+  rules:
+  - http:
+      paths:
+      - path: /
+        pathType: Prefix
+        backend:
+          service:
+            name: {}
+            port:
+              number: 80
+"#, INGRESS_NAME, namespace, service)
+    }
+
+    /// Lists the host ports that the load balancer maps to traefik (the query fails when there is no load balancer).
+    async fn port_mappings(&self) -> Result<Output> {
+        Cmd::new("docker")
+            .arg("port")
+            .arg(format!("k3d-{}-serverlb", self.k3d_cluster_name))
+            .arg("80/tcp")
+            .output(&self.runner).await
+    }
+
+    fn maps_port(&self, mappings: &Output) -> bool {
+        let suffix = format!(":{}", self.port);
+
+        mappings.success && mappings.stdout.lines().any(|l| l.trim().ends_with(&suffix))
+    }
+
+    /// The URL that the UI is reachable at (through the load balancer).
+    pub fn url(&self) -> String {
+        format!("http://localhost:{}", self.port)
+    }
+}
+
+impl Nameable for Ingress {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+#[async_trait]
+impl Ensurable for Ingress {
+    async fn is_present(&self) -> Result<bool> {
+        // The ingress is of no use when the port does not reach the load balancer.
+        if !self.maps_port(&self.port_mappings().await?) {
+            return Ok(false);
+        }
+
+        Cmd::new("kubectl")
+            .arg("get")
+            .arg(format!("ingress/{}", INGRESS_NAME))
+            .arg("-n")
+            .arg(self.backend().0)
+            .succeeds(&self.runner).await
+    }
+
+    async fn make_present(&self) -> Result<()> {
+        // k3d only maps ports when it creates a cluster (and a dry run may not have created it yet).
+        let mappings = self.port_mappings().await?;
+
+        if !self.maps_port(&mappings) && (mappings.success || !is_dry_run()) {
+            return Err(anyhow!("The load balancer of the k3d cluster `{}` does not map port {} (k3d only maps ports when it creates a cluster), so please recreate the cluster with `kfpl service stop`, then `kfpl service start --expose-port {}`.", self.k3d_cluster_name, self.port, self.port));
+        }
+
+        let dir = tempfile::tempdir().context("Unable to create a temp directory for the ingress manifest.")?;
+        let manifest = dir.path().join("ingress.yaml");
+
+        self.runner
+            .write(&manifest, &self.manifest()).await
+            .context("Unable to write the ingress manifest.")?;
+
+        Cmd::new("kubectl")
+            .arg("apply")
+            .arg("-f")
+            .arg(manifest.to_string_lossy())
+            .context("Unable to create the ingress to the UI.")
+            .run(&self.runner).await?;
+
+        verify_present(self, "Unable to verify that the ingress to the UI exists.").await?;
+
+        println!("The UI is available at {}.", self.url());
+
+        Ok(())
+    }
+}
+
+impl Inspectable for Ingress {}
+
+impl Trackable for Ingress {
+    fn state_key(&self) -> String {
+        format!("{} ({})", NAME, self.k3d_cluster_name)
+    }
+
+    fn install_options(&self) -> BTreeMap<String, String> {
+        let mut options = BTreeMap::new();
+        options.insert("k3d_cluster_name".to_owned(), self.k3d_cluster_name.clone());
+        options.insert("kfp_only".to_owned(), self.kfp_only.to_string());
+        options.insert("port".to_owned(), self.port.to_string());
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    static MAPPED: &str = "0.0.0.0:8080\n[::]:8080";
+
+    #[tokio::test]
+    async fn make_present_routes_to_the_kfp_ui() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("docker port k3d-kfp-local-serverlb", MAPPED)
+            .respond("kubectl get ingress/kfpl-ui", "kfpl-ui   <none>   *   80   1s")
+            .into_runner();
+
+        Ingress { runner, ..Default::default() }
+            .with_kfp_only(true)
+            .with_port(8080)
+            .with_k3d_cluster_name("kfp-local")
+            .make_present().await.unwrap();
+
+        let commands = recorder.commands();
+        assert_eq!(commands[0], "docker port k3d-kfp-local-serverlb 80/tcp");
+        let (write, manifest) = commands[1].split_once(" | ").unwrap();
+        assert!(write.starts_with("write ") && write.ends_with("/ingress.yaml"));
+        assert!(manifest.starts_with("apiVersion: networking.k8s.io/v1"));
+        assert!(manifest.contains("namespace: kubeflow"));
+        assert!(manifest.contains("name: ml-pipeline-ui"));
+        assert_eq!(commands[2], format!("kubectl apply -f {}", &write["write ".len()..]));
+        assert_eq!(commands[4], "kubectl get ingress/kfpl-ui -n kubeflow");
+    }
+
+    #[tokio::test]
+    async fn make_present_routes_to_the_istio_gateway() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("docker port k3d-kfp-local-serverlb", MAPPED)
+            .respond("kubectl get ingress/kfpl-ui", "kfpl-ui   <none>   *   80   1s")
+            .into_runner();
+
+        Ingress { runner, ..Default::default() }
+            .with_port(8080)
+            .with_k3d_cluster_name("kfp-local")
+            .make_present().await.unwrap();
+
+        let commands = recorder.commands();
+        assert!(commands[1].contains("namespace: istio-system"));
+        assert!(commands[1].contains("name: istio-ingressgateway"));
+        assert_eq!(commands[4], "kubectl get ingress/kfpl-ui -n istio-system");
+    }
+
+    #[tokio::test]
+    async fn make_present_refuses_a_port_that_the_cluster_does_not_map() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("docker port k3d-kfp-local-serverlb", MAPPED)
+            .into_runner();
+
+        let err = Ingress { runner, ..Default::default() }
+            .with_port(9090)
+            .with_k3d_cluster_name("kfp-local")
+            .make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "The load balancer of the k3d cluster `kfp-local` does not map port 9090 (k3d only maps ports when it creates a cluster), so please recreate the cluster with `kfpl service stop`, then `kfpl service start --expose-port 9090`.");
+        assert_eq!(recorder.commands(), vec!["docker port k3d-kfp-local-serverlb 80/tcp"]);
+    }
+
+    #[tokio::test]
+    async fn is_present_needs_the_port_to_be_mapped() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("docker port k3d-kfp-local-serverlb", MAPPED)
+            .respond("kubectl get ingress/kfpl-ui", "kfpl-ui   <none>   *   80   1s")
+            .into_runner();
+
+        let ingress = Ingress { runner, ..Default::default() }
+            .with_port(9090)
+            .with_k3d_cluster_name("kfp-local");
+
+        assert!(!ingress.is_present().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn make_present_reports_a_failed_apply() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("docker port k3d-kfp-local-serverlb", MAPPED)
+            .fail("kubectl apply")
+            .into_runner();
+
+        let err = Ingress { runner, ..Default::default() }
+            .with_port(8080)
+            .with_k3d_cluster_name("kfp-local")
+            .make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to create the ingress to the UI.");
+    }
+}
@@ -1,52 +1,54 @@
-use tokio::process::Command;
-use anyhow::{Result, Context};
-use async_trait::async_trait;
+use anyhow::Result;
 
-use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
-};
+use crate::services::{download::{Release, ReleaseBinary}, platform::Arch};
 
 static NAME: &str = "k3d";
+static MINIMUM_VERSION: &str = "v4.0.0";
+pub static VERSION: &str = "v4.2.0";
 
-#[derive(Default)]
-pub struct K3d {}
+/// The pinned release binary (rather than the unpinned install script), so that it can be verified against its
+/// checksum.
+pub struct K3dRelease;
 
-impl Nameable for K3d {
-    fn name(&self) -> &'static str {
+pub type K3d = ReleaseBinary<K3dRelease>;
+
+impl Release for K3dRelease {
+    fn name() -> &'static str {
         NAME
     }
-}
 
-#[async_trait]
-impl Ensurable for K3d {
-    async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
+    fn pin() -> &'static str {
+        VERSION
+    }
+
+    fn minimum_version() -> &'static str {
+        MINIMUM_VERSION
+    }
+
+    fn version_args() -> &'static [&'static str] {
+        &["version"]
+    }
+
+    fn url(version: &str, arch: Arch) -> Result<String> {
+        Ok(format!("https://github.com/rancher/k3d/releases/download/{}/k3d-linux-{}", version, arch))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::{command::fake::RecordingRunner, model::Ensurable};
+
+    use super::*;
 
-    async fn make_present(&self) -> Result<()> {
-        Command::new("curl")
-            .arg("-fsSL")
-            .arg("https://raw.githubusercontent.com/rancher/k3d/main/install.sh")
-            .arg("-o")
-            .arg("k3d-install.sh")
-            .status().await
-            .status_to_unit()
-            .context("Unable to curl the k3d convenience script.")?;
-    
-        Command::new("bash")
-            .arg("k3d-install.sh")
-            .status().await
-            .status_to_unit()
-            .context("Failed to run the k3d install script.")?;
-
-        Command::new("rm")
-            .arg("-f")
-            .arg("k3d-install.sh")
-            .status().await
-            .status_to_unit()
-            .context("Failed to delete the k3d install script.")?;
-
-        Ok(())
+    #[tokio::test]
+    async fn make_present_installs_the_pinned_release_binary() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        K3d { runner, ..Default::default() }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "download https://github.com/rancher/k3d/releases/download/v4.2.0/k3d-linux-amd64 | install /usr/local/bin/k3d",
+            "/usr/local/bin/k3d version",
+        ]);
     }
-}
\ No newline at end of file
+}
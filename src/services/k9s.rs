@@ -1,74 +1,91 @@
-use tokio::process::Command;
-use anyhow::{Result, Context};
-use async_trait::async_trait;
+use anyhow::Result;
 
-use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
-};
+use crate::services::{download::{Release, ReleaseBinary}, platform::Arch};
 
 static NAME: &str = "k9s";
+static MINIMUM_VERSION: &str = "v0.22.0";
+pub static VERSION: &str = "v0.22.1";
 
-#[derive(Default)]
-pub struct K9s {}
+pub struct K9sRelease;
 
-impl Nameable for K9s {
-    fn name(&self) -> &'static str {
+pub type K9s = ReleaseBinary<K9sRelease>;
+
+impl Release for K9sRelease {
+    fn name() -> &'static str {
         NAME
     }
-}
 
-#[async_trait]
-impl Ensurable for K9s {
-    async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
+    fn pin() -> &'static str {
+        VERSION
+    }
+
+    fn minimum_version() -> &'static str {
+        MINIMUM_VERSION
+    }
+
+    fn version_args() -> &'static [&'static str] {
+        &["version", "--short"]
+    }
+
+    fn url(version: &str, arch: Arch) -> Result<String> {
+        // The k9s releases name the architectures after `uname -m`.
+        let machine = match arch {
+            Arch::Amd64 => "x86_64",
+            _ => "arm64"
+        };
+
+        Ok(format!("https://github.com/derailed/k9s/releases/download/{}/k9s_Linux_{}.tar.gz", version, machine))
     }
 
-    async fn make_present(&self) -> Result<()> {
-        Command::new("curl")
-            .arg("-LO")
-            .arg("https://github.com/derailed/k9s/releases/download/v0.22.1/k9s_Linux_x86_64.tar.gz")
-            .status().await
-            .status_to_unit()
-            .context("Unable to curl the k9s tarball.")?;
-
-        Command::new("tar")
-            .arg("-xvf")
-            .arg("./k9s_Linux_x86_64.tar.gz")
-            .status().await
-            .status_to_unit()
-            .context("Unable to untar the k9s tarball.")?;
-
-        Command::new("rm")
-            .arg("-f")
-            .arg("k9s_Linux_x86_64.tar.gz")
-            .arg("LICENSE")
-            .arg("README.md")
-            .status().await
-            .status_to_unit()
-            .context("Unable to remove the k9s tarball.")?;
-    
-        Command::new("chmod")
-            .arg("+x")
-            .arg("./k9s")
-            .status().await
-            .status_to_unit()
-            .context("Unable to change executable permissions on the k9s binary.")?;
-
-        Command::new("mv")
-            .arg("./k9s")
-            .arg("/usr/local/bin/k9s")
-            .status().await
-            .status_to_unit()
-            .context("Unable to copy the k9s binary (might need sudo).")?;
-
-        Command::new("k9s")
-            .arg("version")
-            .status().await
-            .status_to_unit()
-            .context("Unable to use k9s after supposed install.")?;
-
-        Ok(())
+    fn member() -> Option<&'static str> {
+        Some(NAME)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::services::{command::fake::RecordingRunner, model::Ensurable};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn make_present_installs_the_release_tarball() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        K9s { runner, ..Default::default() }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "download https://github.com/derailed/k9s/releases/download/v0.22.1/k9s_Linux_x86_64.tar.gz | extract k9s | install /usr/local/bin/k9s",
+            "/usr/local/bin/k9s version --short",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_picks_the_arm64_tarball() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        K9s { runner, arch: Arch::Arm64, ..Default::default() }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands()[0], "download https://github.com/derailed/k9s/releases/download/v0.22.1/k9s_Linux_arm64.tar.gz | extract k9s | install /usr/local/bin/k9s");
+    }
+
+    #[tokio::test]
+    async fn make_present_reports_a_failed_download() {
+        let (runner, _) = RecordingRunner::default().fail("download").into_runner();
+
+        let err = K9s { runner, ..Default::default() }.make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to install the k9s binary (might need sudo).");
+    }
+
+    #[tokio::test]
+    async fn is_present_looks_in_the_prefix() {
+        let (runner, recorder) = RecordingRunner::default().respond("test -x /home/kfpl/.local/bin/k9s", "").into_runner();
+
+        let k9s = K9s { runner, ..Default::default() }.with_prefix("/home/kfpl/.local/bin");
+
+        assert!(k9s.is_present().await.unwrap());
+        assert!(!k9s.with_prefix("/usr/local/bin").is_present().await.unwrap());
+        assert_eq!(recorder.commands(), vec!["test -x /home/kfpl/.local/bin/k9s", "test -x /usr/local/bin/k9s"]);
+    }
+}
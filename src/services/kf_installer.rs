@@ -0,0 +1,38 @@
+use std::{fmt, str::FromStr};
+use anyhow::{Error, anyhow};
+use serde::Deserialize;
+
+pub static KF_INSTALLERS: [&str; 2] = ["manifests", "kfctl"];
+
+/// How KubeFlow is deployed: by applying the `kubeflow/manifests` example with `kustomize` (v1.3 and later), or by
+/// applying a kfdef with `kfctl` (v1.2 and earlier, since `kfctl` is deprecated upstream).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KfInstaller {
+    #[default]
+    Manifests,
+    Kfctl,
+}
+
+impl FromStr for KfInstaller {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manifests" => Ok(KfInstaller::Manifests),
+            "kfctl" => Ok(KfInstaller::Kfctl),
+            other => Err(anyhow!("`{}` is not a KubeFlow installer (use one of {}).", other, KF_INSTALLERS.join(", ")))
+        }
+    }
+}
+
+impl fmt::Display for KfInstaller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            KfInstaller::Manifests => "manifests",
+            KfInstaller::Kfctl => "kfctl",
+        };
+
+        write!(f, "{}", name)
+    }
+}
@@ -1,27 +1,437 @@
-use tokio::process::Command;
-use anyhow::{Result, Context, Error};
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, sync::Mutex, time::Instant};
+use anyhow::{Result, Context, anyhow};
 use async_trait::async_trait;
+use serde_yaml::Value;
+use tokio::time::Duration;
+use yansi::Paint;
 
 use crate::{
-    services::model::{Nameable, Ensurable},
-    helpers::ExitStatusIntoUnit
+    services::model::{Nameable, Ensurable, Inspectable, Trackable, Bundleable, Readiness, verify_present},
+    services::command::{Cmd, Runner, quote, is_dry_run},
+    services::rollout::Rollout,
+    services::kube::is_deployment_available,
+    services::kf_installer::KfInstaller,
+    bundle::{self, BIN_DIR, MANIFESTS_DIR}
 };
 
 static NAME: &str = "KF Service";
-// TODO: Fix this.
-static SERVICE_NAME: &str = "ml-pipeline";
 static TEMP_FOLDER: &str = "kftemp409231";
+static PARTIAL_KFDEF: &str = "kfdef.yaml";
+static PICKED_KUSTOMIZATION: &str = "kfpl";
+static BUNDLED_MANIFESTS: &str = "kf.yaml";
+static MANIFESTS_REPO: &str = "https://github.com/kubeflow/manifests.git";
+static DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+static RETRY_DELAY: Duration = Duration::from_secs(10);
+/// The apply errors of resources whose definitions (or admission webhooks) are not ready yet, which go away on retry.
+static SETTLING_ERRORS: &[&str] = &["no matches for kind", "ensure CRDs are installed first", "failed calling webhook"];
+pub static MANIFESTS_VERSION: &str = "v1.3.0";
+
+/// The components that can be picked (the rest are their dependencies), where `kserve` is the later name of
+/// `kfserving`.
+pub static KF_COMPONENTS: [&str; 8] = ["pipelines", "central-dashboard", "notebooks", "katib", "kfserving", "kserve", "tensorboards", "volumes-web-app"];
+
+/// The applications of the default kfdef (`kfctl_k8s_istio` v1.2), which are expected when the kfdef cannot be read.
+static DEFAULT_KFDEF_APPLICATIONS: &[&str] = &[
+    "namespaces", "istio-stack", "cluster-local-gateway", "istio", "cert-manager-crds", "cert-manager-kube-system-resources",
+    "cert-manager", "add-anonymous-user-filter", "application", "bootstrap", "spark-operator", "kubeflow-apps", "knative", "kfserving"
+];
+
+/// The `kubeflow/manifests` resources that every picked set of components shares.
+static BASE_RESOURCES: &[&str] = &["common/kubeflow-namespace/base", "common/kubeflow-roles/base"];
+
+/// A KubeFlow component, which a kfdef installs when it has one of the `applications` (v1.2 bundles most of them
+/// into `kubeflow-apps`), and which is present once its deployment is available.  Picked on its own, it is deployed
+/// from its `resources` (paths in `kubeflow/manifests`, as of v1.3), along with the components it `requires`.
+#[derive(Debug)]
+struct Component {
+    id: &'static str,
+    name: &'static str,
+    applications: &'static [&'static str],
+    namespace: &'static str,
+    deployment: &'static str,
+    resources: &'static [&'static str],
+    requires: &'static [&'static str],
+}
+
+static COMPONENTS: &[Component] = &[
+    Component {
+        id: "istio", name: "istio", applications: &["istio", "istio-stack"], namespace: "istio-system", deployment: "istio-ingressgateway",
+        resources: &["common/istio-1-9/istio-crds/base", "common/istio-1-9/istio-namespace/base", "common/istio-1-9/istio-install/base", "common/istio-1-9/kubeflow-istio-resources/base"],
+        requires: &[]
+    },
+    Component {
+        id: "dex", name: "dex", applications: &["dex"], namespace: "auth", deployment: "dex",
+        resources: &["common/oidc-authservice/base", "common/dex/overlays/istio"],
+        requires: &["istio"]
+    },
+    Component {
+        id: "central-dashboard", name: "central dashboard", applications: &["centraldashboard", "kubeflow-apps"], namespace: "kubeflow", deployment: "centraldashboard",
+        resources: &["apps/centraldashboard/upstream/overlays/istio"],
+        requires: &["profiles"]
+    },
+    Component {
+        id: "notebooks", name: "notebooks", applications: &["notebook-controller", "kubeflow-apps"], namespace: "kubeflow", deployment: "notebook-controller-deployment",
+        resources: &["apps/admission-webhook/upstream/overlays/cert-manager", "apps/jupyter/jupyter-web-app/upstream/overlays/istio", "apps/jupyter/notebook-controller/upstream/overlays/kubeflow"],
+        requires: &["profiles", "cert-manager"]
+    },
+    Component {
+        id: "katib", name: "katib", applications: &["katib", "katib-controller", "kubeflow-apps"], namespace: "kubeflow", deployment: "katib-controller",
+        resources: &["apps/katib/upstream/installs/katib-with-kubeflow"],
+        requires: &["profiles"]
+    },
+    Component {
+        id: "profiles", name: "profiles", applications: &["profiles", "kubeflow-apps"], namespace: "kubeflow", deployment: "profiles-deployment",
+        resources: &["apps/profiles/upstream/overlays/kubeflow", "common/user-namespace/base"],
+        requires: &["istio", "dex"]
+    },
+    Component {
+        id: "pipelines", name: "pipelines", applications: &["pipeline", "api-service", "kubeflow-apps"], namespace: "kubeflow", deployment: "ml-pipeline",
+        resources: &["apps/pipeline/upstream/env/platform-agnostic-multi-user"],
+        requires: &["profiles"]
+    },
+    Component {
+        id: "cert-manager", name: "cert-manager", applications: &["cert-manager"], namespace: "cert-manager", deployment: "cert-manager",
+        resources: &["common/cert-manager/cert-manager/base", "common/cert-manager/kubeflow-issuer/base"],
+        requires: &[]
+    },
+    Component {
+        // The kfserving controller of a kfdef (v1.2) is a stateful set, so it is not expected for one.
+        id: "kfserving", name: "kfserving", applications: &[], namespace: "kubeflow", deployment: "kfserving-models-web-app",
+        resources: &["common/knative/knative-serving/base", "common/istio-1-9/cluster-local-gateway/base", "apps/kfserving/upstream/overlays/kubeflow"],
+        requires: &["profiles", "cert-manager"]
+    },
+    Component {
+        id: "tensorboards", name: "tensorboards", applications: &["tensorboard-controller", "tensorboards-web-app"], namespace: "kubeflow", deployment: "tensorboards-web-app-deployment",
+        resources: &["apps/tensorboard/tensorboards-web-app/upstream/overlays/istio", "apps/tensorboard/tensorboard-controller/upstream/overlays/kubeflow"],
+        requires: &["profiles"]
+    },
+    Component {
+        id: "volumes-web-app", name: "volumes web app", applications: &["volumes-web-app"], namespace: "kubeflow", deployment: "volumes-web-app-deployment",
+        resources: &["apps/volumes-web-app/upstream/overlays/istio"],
+        requires: &["profiles"]
+    },
+];
+
+/// Refuses to pick components out of a `kubeflow/manifests` release other than v1.3 (whose layout the resources of the
+/// table follow), or to pick unknown components.
+pub fn check_components(manifests_version: &str, components: &[String]) -> Result<()> {
+    if components.is_empty() {
+        return Ok(());
+    }
+
+    if !manifests_version.starts_with("v1.3.") && manifests_version != "v1.3-branch" {
+        return Err(anyhow!("Picking the KubeFlow components needs the v1.3 manifests (rather than {}), so please use `--kf-manifests-version v1.3.0`, or deploy every component.", manifests_version));
+    }
+
+    resolve(components).map(|_| ())
+}
+
+/// Resolves the picked components (by id), along with every component that they require, in the order of the table.
+fn resolve(ids: &[String]) -> Result<Vec<&'static Component>> {
+    let mut picked: Vec<&str> = Vec::new();
+
+    for id in ids {
+        if !KF_COMPONENTS.contains(&id.as_str()) {
+            return Err(anyhow!("`{}` is not a KubeFlow component (use one of {}).", id, KF_COMPONENTS.join(", ")));
+        }
+
+        picked.push(if id == "kserve" { "kfserving" } else { id });
+    }
+
+    // The list grows while it is walked, so the requirements of the requirements are picked as well.
+    let mut i = 0;
+    while i < picked.len() {
+        // SAFETY: unwrap is safe because every id (and requirement) is in the table.
+        let component = COMPONENTS.iter().find(|c| c.id == picked[i]).unwrap();
+
+        for required in component.requires {
+            if !picked.contains(required) {
+                picked.push(required);
+            }
+        }
+
+        i += 1;
+    }
+
+    Ok(COMPONENTS.iter().filter(|c| picked.contains(&c.id)).collect())
+}
 
-#[derive(Default)]
 pub struct KfService {
-    kf_yaml: String
+    installer: KfInstaller,
+    manifests_version: String,
+    components: Vec<String>,
+    kf_yaml: String,
+    k3d_cluster_name: String,
+    bundle_dir: Option<PathBuf>,
+    wait_timeout: Option<Duration>,
+    /// The kfdef, once it was read (`None` when it could not be).
+    kfdef: Mutex<Option<Option<Value>>>,
+    runner: Runner
+}
+
+impl Default for KfService {
+    fn default() -> Self {
+        Self {
+            installer: KfInstaller::default(),
+            manifests_version: MANIFESTS_VERSION.to_owned(),
+            components: Vec::new(),
+            kf_yaml: String::new(),
+            k3d_cluster_name: String::new(),
+            bundle_dir: None,
+            wait_timeout: None,
+            kfdef: Mutex::default(),
+            runner: Runner::default()
+        }
+    }
 }
 
 impl KfService {
+    /// Sets whether KubeFlow is deployed from the `kubeflow/manifests` example, or from the kfdef with `kfctl`.
+    pub fn with_installer(mut self, i: KfInstaller) -> Self {
+        self.installer = i;
+        self
+    }
+
+    /// Sets the `kubeflow/manifests` tag (or branch) that is deployed (only applies to the manifests installer).
+    pub fn with_manifests_version(mut self, v: &str) -> Self {
+        self.manifests_version = v.to_owned();
+        self
+    }
+
+    /// Picks the components that are deployed (with their dependencies), rather than every one in the manifests
+    /// example (only applies to the manifests installer).
+    pub fn with_components(mut self, c: &[String]) -> Self {
+        self.components = c.to_vec();
+        self
+    }
+
+    /// Sets the kfdef that is deployed (only applies to the kfctl installer).
     pub fn with_kf_yaml(mut self, n: &str) -> Self {
         self.kf_yaml = n.to_owned();
         self
     }
+
+    /// Names the cluster that the deployment is recorded against.
+    pub fn with_k3d_cluster_name(mut self, n: &str) -> Self {
+        self.k3d_cluster_name = n.to_owned();
+        self
+    }
+
+    /// Applies the manifests rendered into an (unpacked) offline bundle, rather than the ones on GitHub (only applies
+    /// to the manifests installer).
+    pub fn with_bundle(mut self, b: Option<&Path>) -> Self {
+        self.bundle_dir = b.map(Path::to_owned);
+        self
+    }
+
+    /// Sets how long to wait for the rollout overall (rather than the default of 10 minutes).
+    pub fn with_wait_timeout(mut self, t: Option<Duration>) -> Self {
+        self.wait_timeout = t;
+        self
+    }
+
+    fn manifest_path(dir: &Path) -> PathBuf {
+        dir.join(MANIFESTS_DIR).join(BUNDLED_MANIFESTS)
+    }
+
+    /// Reads the kfdef (if it is reachable, and there is one: the manifests example installs every component), only
+    /// once, since `ensure` and `status` ask for the components several times.
+    async fn kfdef(&self) -> Result<Option<Value>> {
+        if self.installer == KfInstaller::Manifests {
+            return Ok(None);
+        }
+
+        // SAFETY: unwrap is safe because the lock is never held across a panic.
+        if let Some(kfdef) = self.kfdef.lock().unwrap().clone() {
+            return Ok(kfdef);
+        }
+
+        let output = self.runner.read(&self.kf_yaml).await?;
+
+        let kfdef = if output.success {
+            Some(serde_yaml::from_str(&output.stdout).with_context(|| format!("Unable to parse the kfdef `{}`.", self.kf_yaml))?)
+        } else {
+            None
+        };
+
+        *self.kfdef.lock().unwrap() = Some(kfdef.clone());
+
+        Ok(kfdef)
+    }
+
+    /// Returns the components that were picked, or that the manifests example (every one) or the kfdef installs (the
+    /// ones of the default kfdef, if it cannot be read).
+    fn expected(&self, kfdef: Option<&Value>) -> Result<Vec<&'static Component>> {
+        let applications = match self.installer {
+            KfInstaller::Manifests if !self.components.is_empty() => return resolve(&self.components),
+            KfInstaller::Manifests => return Ok(COMPONENTS.iter().collect()),
+            KfInstaller::Kfctl => kfdef.map(applications).unwrap_or_else(|| DEFAULT_KFDEF_APPLICATIONS.iter().map(|a| a.to_string()).collect())
+        };
+
+        Ok(COMPONENTS.iter().filter(|c| c.applications.iter().any(|a| applications.iter().any(|name| name == a))).collect())
+    }
+
+    /// Returns the components that the kfdef installs, and which of them are missing.
+    async fn components(&self) -> Result<(Vec<&'static Component>, Vec<&'static Component>)> {
+        self.components_of(self.kfdef().await?.as_ref()).await
+    }
+
+    async fn components_of(&self, kfdef: Option<&Value>) -> Result<(Vec<&'static Component>, Vec<&'static Component>)> {
+        let expected = self.expected(kfdef)?;
+
+        let mut missing = Vec::new();
+        for component in &expected {
+            if !is_deployment_available(&self.runner, component.namespace, component.deployment).await? {
+                missing.push(*component);
+            }
+        }
+
+        Ok((expected, missing))
+    }
+
+    /// Applies the kfdef with `kfctl` (only the applications of the missing components, for a partial deployment).
+    async fn apply_kfdef(&self, kfdef: Option<Value>, missing: &[&Component], present: &[&Component]) -> Result<()> {
+        let kfdef_path = match kfdef {
+            Some(kfdef) if !present.is_empty() => {
+                let names: Vec<&str> = missing.iter().map(|c| c.name).collect();
+                println!("Installing only the missing KubeFlow components ({}) ...", names.join(", "));
+
+                self.runner
+                    .write(&Path::new(TEMP_FOLDER).join(PARTIAL_KFDEF), &serde_yaml::to_string(&without_present(kfdef, present))?).await
+                    .context("Unable to write the partial kfdef.")?;
+
+                PARTIAL_KFDEF.to_owned()
+            },
+            _ => self.kf_yaml.clone()
+        };
+
+        Cmd::new("sh")
+            .arg("-c")
+            .arg(format!("cd {} && kfctl apply -V -f {}", TEMP_FOLDER, kfdef_path))
+            .context("Unable to apply the KF kustomize script.")
+            .run(&self.runner).await
+    }
+
+    /// Fetches `kubeflow/manifests` at the tag into `manifests`, and returns the kustomization to build: the example, or
+    /// one of the picked components (written next to it).
+    async fn checkout_manifests(&self, manifests: &str, expected: &[&Component]) -> Result<&'static str> {
+        // A failed run leaves its checkout behind, which would stop the clone.
+        Cmd::new("rm")
+            .arg("-rf")
+            .arg(manifests)
+            .context("Unable to remove the previous KubeFlow manifests.")
+            .run(&self.runner).await?;
+
+        Cmd::new("git")
+            .args(["clone", "--depth", "1", "--branch", &self.manifests_version, MANIFESTS_REPO, manifests])
+            .context(&format!("Unable to fetch the KubeFlow manifests ({}).", self.manifests_version))
+            .run(&self.runner).await?;
+
+        if self.components.is_empty() {
+            return Ok("example");
+        }
+
+        let names: Vec<&str> = expected.iter().map(|c| c.name).collect();
+        println!("Building a kustomization of the picked KubeFlow components, and their dependencies ({}) ...", names.join(", "));
+
+        Cmd::new("mkdir")
+            .arg("-p")
+            .arg(format!("{}/{}", manifests, PICKED_KUSTOMIZATION))
+            .context("Unable to create the kustomization directory.")
+            .run(&self.runner).await?;
+
+        self.runner
+            .write(&Path::new(manifests).join(PICKED_KUSTOMIZATION).join("kustomization.yaml"), &picked_kustomization(expected)).await
+            .context("Unable to write the kustomization of the picked components.")?;
+
+        Ok(PICKED_KUSTOMIZATION)
+    }
+
+    /// Applies the example (or a kustomization of the picked components) with `kustomize`, or the rendering of it out
+    /// of the bundle.  The custom resources of the first pass fail until their definitions are established, so the
+    /// apply is retried (only) on those errors, until it goes through or the timeout runs out.
+    async fn apply_manifests(&self, expected: &[&Component]) -> Result<()> {
+        let apply = match &self.bundle_dir {
+            Some(dir) => Cmd::new("kubectl")
+                .arg("apply")
+                .arg("-f")
+                .arg(Self::manifest_path(dir).to_string_lossy()),
+            None => {
+                let manifests = format!("{}/manifests", TEMP_FOLDER);
+                let kustomization = self.checkout_manifests(&manifests, expected).await?;
+
+                Cmd::new("sh")
+                    .arg("-c")
+                    .arg(format!("kustomize build {}/{} | kubectl apply -f -", manifests, kustomization))
+            }
+        }.context("Unable to apply the KubeFlow manifests.");
+
+        // The apply is captured (to tell the settling errors apart), so it is only planned during a dry run.
+        if is_dry_run() {
+            return apply.run(&self.runner).await;
+        }
+
+        let timeout = self.wait_timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let start = Instant::now();
+        let mut waited = Duration::from_secs(0);
+
+        loop {
+            let output = apply.clone().output(&self.runner).await?;
+
+            if output.success {
+                return Ok(());
+            }
+
+            // The waits are counted as well, since a test runner does not actually pause.
+            let elapsed = start.elapsed().max(waited);
+            let is_settling = SETTLING_ERRORS.iter().any(|e| output.stderr.contains(e));
+
+            if !is_settling || elapsed >= timeout {
+                return Err(anyhow!("{}", output.stderr.trim())).context("Unable to apply the KubeFlow manifests.");
+            }
+
+            println!("{}, so retrying in {}s (after {}s) ...", Paint::yellow("Some KubeFlow resources are not established yet"), RETRY_DELAY.as_secs(), elapsed.as_secs());
+
+            self.runner.wait(RETRY_DELAY).await;
+            waited += RETRY_DELAY;
+        }
+    }
+}
+
+/// Returns the names of the applications in a kfdef.
+fn applications(kfdef: &Value) -> Vec<String> {
+    kfdef["spec"]["applications"].as_sequence()
+        .map(|apps| apps.iter().filter_map(|a| a["name"].as_str().map(str::to_owned)).collect())
+        .unwrap_or_default()
+}
+
+/// Writes a kustomization (next to the example, in `kubeflow/manifests`) of the shared resources, and the resources of
+/// the components.
+fn picked_kustomization(components: &[&Component]) -> String {
+    let resources = BASE_RESOURCES.iter().chain(components.iter().flat_map(|c| c.resources));
+
+    let mut kustomization = "apiVersion: kustomize.config.k8s.io/v1beta1\nkind: Kustomization\nresources:\n".to_owned();
+    for resource in resources {
+        kustomization.push_str(&format!("- ../{}\n", resource));
+    }
+
+    kustomization
+}
+
+/// Drops the applications that only install present components from a kfdef (the rest, including the shared ones
+/// such as `namespaces`, are applied again, which `kfctl` tolerates).
+fn without_present(mut kfdef: Value, present: &[&Component]) -> Value {
+    let is_redundant = |name: &str| {
+        let owners: Vec<&Component> = COMPONENTS.iter().filter(|c| c.applications.contains(&name)).collect();
+
+        !owners.is_empty() && owners.iter().all(|o| present.iter().any(|p| p.name == o.name))
+    };
+
+    if let Some(apps) = kfdef["spec"]["applications"].as_sequence_mut() {
+        apps.retain(|a| !a["name"].as_str().map(is_redundant).unwrap_or(false));
+    }
+
+    kfdef
 }
 
 impl Nameable for KfService {
@@ -33,57 +443,375 @@ impl Nameable for KfService {
 #[async_trait]
 impl Ensurable for KfService {
     async fn is_present(&self) -> Result<bool> {
-        let k_out = Command::new("kubectl")
-            .arg("get")
-            .arg("pods")
-            .arg("--all-namespaces")
-            .output().await?.stdout;
-        let k_out_str = std::str::from_utf8(&k_out)?;
+        let (expected, missing) = self.components().await?;
 
-        Ok(k_out_str.contains(SERVICE_NAME))
+        Ok(!expected.is_empty() && missing.is_empty())
     }
 
     async fn make_present(&self) -> Result<()> {
-        Command::new("mkdir")
+        let kfdef = self.kfdef().await?;
+        let (expected, missing) = self.components_of(kfdef.as_ref()).await?;
+        let present: Vec<&Component> = expected.iter().filter(|c| !missing.iter().any(|m| m.name == c.name)).copied().collect();
+
+        Cmd::new("mkdir")
             .arg("-p")
             .arg(TEMP_FOLDER)
-            .status().await
-            .status_to_unit()
-            .context("Unable to create a KubeFlow temp directory.")?;
+            .context("Unable to create a KubeFlow temp directory.")
+            .run(&self.runner).await?;
 
-        Command::new("sh")
-            .arg("-c")
-            .arg(format!("cd {}; kfctl apply -V -f {}", TEMP_FOLDER, self.kf_yaml))
-            .status().await
-            .status_to_unit()
-            .context("Unable to apply the KF kustomize script.")?;
-
-        tokio::time::delay_for(tokio::time::Duration::from_secs(10)).await;
-
-        println!("Waiting for the ml-pipeline deployment to complete ...");
-
-        Command::new("kubectl")
-            .arg("wait")
-            .arg("--for=condition=available")
-            .arg("--timeout=600s")
-            .arg("deploy/ml-pipeline")
-            .arg("-n")
-            .arg("kubeflow")
-            .status().await
-            .status_to_unit()
-            .context("Unable to wait for the ml-pipeline deployment to come up.")?;
-
-        Command::new("rm")
+        match self.installer {
+            KfInstaller::Manifests => self.apply_manifests(&expected).await?,
+            KfInstaller::Kfctl => self.apply_kfdef(kfdef, &missing, &present).await?
+        }
+
+        println!("Waiting for the KubeFlow components to come up ...");
+
+        let rollout = expected.iter().fold(Rollout::new(), |r, c| r.deployment(c.namespace, c.deployment));
+
+        rollout
+            .timeout(self.wait_timeout)
+            .context("Unable to wait for the KubeFlow components to come up.")
+            .run(&self.runner).await?;
+
+        Cmd::new("rm")
             .arg("-rf")
             .arg(TEMP_FOLDER)
-            .status().await
-            .status_to_unit()
-            .context("Unable to remove the KF temp directory.")?;
+            .context("Unable to remove the KF temp directory.")
+            .run(&self.runner).await?;
 
-        if self.is_present().await? {
-            Ok(())
-        } else {
-            Err(Error::msg("Unable to verify that the kf service is running."))
+        verify_present(self, "Unable to verify that the kf service is running.").await
+    }
+}
+
+#[async_trait]
+impl Inspectable for KfService {
+    async fn readiness(&self) -> Result<Option<Readiness>> {
+        let (expected, missing) = self.components().await?;
+
+        if missing.len() == expected.len() {
+            return Ok(None);
         }
+
+        Ok(Some(Readiness { kind: "components", ready: expected.len() - missing.len(), total: expected.len() }))
+    }
+
+    async fn missing(&self) -> Result<Option<String>> {
+        let (_, missing) = self.components().await?;
+
+        if missing.is_empty() {
+            return Ok(None);
+        }
+
+        let names: Vec<&str> = missing.iter().map(|c| c.name).collect();
+
+        Ok(Some(format!("missing {}", names.join(", "))))
+    }
+}
+
+#[async_trait]
+impl Bundleable for KfService {
+    /// Renders the example (or the picked components) with the bundled `kustomize`, so that a deploy out of the bundle
+    /// only has to apply it (and the images that it uses are read out of it).
+    async fn make_bundled(&self, dir: &Path) -> Result<()> {
+        let checkout = tempfile::tempdir().context("Unable to create a temp directory for the KubeFlow manifests.")?;
+        let manifests = checkout.path().join("manifests").to_string_lossy().into_owned();
+        let kustomization = self.checkout_manifests(&manifests, &self.expected(None)?).await?;
+
+        fs::create_dir_all(dir.join(MANIFESTS_DIR))?;
+
+        Cmd::new("sh")
+            .arg("-c")
+            .arg(format!("{} build {} > {}",
+                quote(&bundle::file(dir, BIN_DIR, "kustomize").to_string_lossy()),
+                quote(&format!("{}/{}", manifests, kustomization)),
+                quote(&Self::manifest_path(dir).to_string_lossy())))
+            .context("Unable to render the KubeFlow manifests.")
+            .run(&self.runner).await
+    }
+}
+
+impl Trackable for KfService {
+    fn state_key(&self) -> String {
+        format!("{} ({})", NAME, self.k3d_cluster_name)
+    }
+
+    fn install_options(&self) -> BTreeMap<String, String> {
+        let mut options = BTreeMap::new();
+        options.insert("k3d_cluster_name".to_owned(), self.k3d_cluster_name.clone());
+        options.insert("installer".to_owned(), self.installer.to_string());
+
+        match self.installer {
+            KfInstaller::Manifests => {
+                if !self.components.is_empty() {
+                    options.insert("components".to_owned(), self.components.join(","));
+                }
+
+                options.insert("manifests_version".to_owned(), self.manifests_version.clone())
+            },
+            KfInstaller::Kfctl => options.insert("kf_yaml".to_owned(), self.kf_yaml.clone())
+        };
+
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    static KF_YAML: &str = "https://example.com/kfctl_k8s_istio.yaml";
+    static KFDEF: &str = "apiVersion: kfdef.apps.kubeflow.org/v1
+kind: KfDef
+spec:
+  applications:
+  - name: namespaces
+  - name: istio
+  - name: kubeflow-apps
+";
+    static AVAILABLE: &str = r#"{"status": {"conditions": [{"type": "Available", "status": "True"}]}}"#;
+
+    fn kf_service(runner: Runner) -> KfService {
+        KfService { runner, ..Default::default() }.with_installer(KfInstaller::Kfctl).with_kf_yaml(KF_YAML)
+    }
+
+    #[tokio::test]
+    async fn is_present_ignores_a_kfp_only_deployment() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("read", KFDEF)
+            .respond("GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline", AVAILABLE)
+            .into_runner();
+
+        let service = kf_service(runner);
+
+        assert!(!service.is_present().await.unwrap());
+        assert_eq!(service.missing().await.unwrap().as_deref(), Some("missing istio, central dashboard, notebooks, katib, profiles"));
+    }
+
+    #[tokio::test]
+    async fn is_present_only_expects_the_components_of_the_kfdef() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("read", KFDEF)
+            .respond("GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        assert!(kf_service(runner).is_present().await.unwrap());
+        assert!(!recorder.commands().contains(&"GET /apis/apps/v1/namespaces/auth/deployments/dex".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn is_present_falls_back_to_the_default_kfdef_once() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        let service = kf_service(runner);
+
+        assert!(service.is_present().await.unwrap());
+        assert_eq!(service.missing().await.unwrap(), None);
+
+        let commands = recorder.commands();
+        assert_eq!(commands.iter().filter(|c| c.starts_with("read")).count(), 1);
+        assert!(commands.contains(&"GET /apis/apps/v1/namespaces/cert-manager/deployments/cert-manager".to_owned()));
+        assert!(!commands.contains(&"GET /apis/apps/v1/namespaces/auth/deployments/dex".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn make_present_applies_the_kfdef_from_a_temp_dir() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("read", "spec:\n  applications:\n  - name: pipeline\n")
+            .respond("kubectl get deploy/", "True")
+            .respond_after("sh -c 'cd kftemp409231 && kfctl", "GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        kf_service(runner).make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), [
+            format!("read {}", KF_YAML),
+            "GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline".to_owned(),
+            "mkdir -p kftemp409231".to_owned(),
+            format!("sh -c 'cd kftemp409231 && kfctl apply -V -f {}'", KF_YAML),
+            r#"kubectl get deploy/ml-pipeline -n kubeflow -o 'jsonpath={.status.conditions[?(@.type=="Available")].status}'"#.to_owned(),
+            "rm -rf kftemp409231".to_owned(),
+            "GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline".to_owned(),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_installs_only_the_missing_components() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("read", "spec:\n  applications:\n  - name: namespaces\n  - name: istio\n  - name: katib\n")
+            .respond("GET /apis/apps/v1/namespaces/istio-system/", AVAILABLE)
+            .respond("kubectl get deploy/", "True")
+            .respond_after("sh -c 'cd kftemp409231 && kfctl", "GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        kf_service(runner).make_present().await.unwrap();
+
+        let commands = recorder.commands();
+        let write = commands.iter().find(|c| c.starts_with("write kftemp409231/kfdef.yaml | ")).unwrap();
+        assert!(write.contains("name: namespaces") && write.contains("name: katib") && !write.contains("name: istio"));
+        assert!(commands.contains(&"sh -c 'cd kftemp409231 && kfctl apply -V -f kfdef.yaml'".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn make_present_reports_a_failed_apply() {
+        let (runner, _) = RecordingRunner::default().fail("sh -c").into_runner();
+
+        let err = kf_service(runner).make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to apply the KF kustomize script.");
+    }
+
+    static APPLY: &str = "sh -c 'kustomize build kftemp409231/manifests/example | kubectl apply -f -'";
+    static NO_MATCHES: &str = r#"error: unable to recognize "STDIN": no matches for kind "Certificate" in version "cert-manager.io/v1alpha2""#;
+
+    #[tokio::test]
+    async fn make_present_applies_the_manifests_example_until_it_goes_through() {
+        let (runner, recorder) = RecordingRunner::default()
+            .fail_query(APPLY, NO_MATCHES, 1)
+            .respond(APPLY, "")
+            .respond("kubectl get deploy/", "True")
+            .respond_after(APPLY, "GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        KfService { runner, ..Default::default() }
+            .with_manifests_version("v1.3.1")
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands().iter().filter(|c| !c.starts_with("GET") && !c.starts_with("kubectl")).collect::<Vec<_>>(), [
+            "mkdir -p kftemp409231",
+            "rm -rf kftemp409231/manifests",
+            "git clone --depth 1 --branch v1.3.1 https://github.com/kubeflow/manifests.git kftemp409231/manifests",
+            APPLY,
+            APPLY,
+            "rm -rf kftemp409231",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_stops_retrying_the_manifests_at_the_timeout() {
+        let (runner, recorder) = RecordingRunner::default()
+            .fail_query(APPLY, NO_MATCHES, usize::MAX)
+            .into_runner();
+
+        let err = KfService { runner, ..Default::default() }
+            .with_wait_timeout(Some(Duration::from_secs(20)))
+            .make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to apply the KubeFlow manifests.");
+        assert_eq!(format!("{:#}", err), format!("Unable to apply the KubeFlow manifests.: {}", NO_MATCHES));
+        assert_eq!(recorder.commands().iter().filter(|c| *c == APPLY).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn make_present_does_not_retry_other_apply_errors() {
+        let (runner, recorder) = RecordingRunner::default()
+            .fail_query(APPLY, "sh: 1: kustomize: not found\n", 1)
+            .into_runner();
+
+        let err = KfService { runner, ..Default::default() }.make_present().await.unwrap_err();
+
+        assert_eq!(format!("{:#}", err), "Unable to apply the KubeFlow manifests.: sh: 1: kustomize: not found");
+        assert_eq!(recorder.commands().iter().filter(|c| *c == APPLY).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn is_present_expects_every_component_of_the_manifests_example() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        assert!(KfService { runner, ..Default::default() }.is_present().await.unwrap());
+        assert!(!recorder.commands().iter().any(|c| c.starts_with("read")));
+        assert!(recorder.commands().contains(&"GET /apis/apps/v1/namespaces/auth/deployments/dex".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn make_present_applies_a_kustomization_of_the_picked_components() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("sh -c 'kustomize build", "")
+            .respond("kubectl get deploy/", "True")
+            .respond_after("sh -c 'kustomize build", "GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        KfService { runner, ..Default::default() }
+            .with_components(&["katib".to_owned()])
+            .make_present().await.unwrap();
+
+        let commands = recorder.commands();
+        let write = commands.iter().find(|c| c.starts_with("write kftemp409231/manifests/kfpl/kustomization.yaml | ")).unwrap();
+        assert!(write.contains("- ../common/kubeflow-namespace/base") && write.contains("- ../common/dex/overlays/istio") && write.contains("- ../apps/katib/upstream/installs/katib-with-kubeflow"));
+        assert!(!write.contains("pipeline"));
+        assert!(commands.contains(&"sh -c 'kustomize build kftemp409231/manifests/kfpl | kubectl apply -f -'".to_owned()));
+        assert!(!commands.contains(&"GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline".to_owned()));
+        assert!(commands.contains(&"GET /apis/apps/v1/namespaces/kubeflow/deployments/katib-controller".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn make_present_applies_the_bundled_manifests() {
+        let bundled = "kubectl apply -f /tmp/bundle/manifests/kf.yaml";
+        let (runner, recorder) = RecordingRunner::default()
+            .respond(bundled, "")
+            .respond("kubectl get deploy/", "True")
+            .respond_after(bundled, "GET /apis/apps/v1/namespaces/", AVAILABLE)
+            .into_runner();
+
+        KfService { runner, ..Default::default() }
+            .with_bundle(Some(Path::new("/tmp/bundle")))
+            .make_present().await.unwrap();
+
+        let commands = recorder.commands();
+        assert!(commands.contains(&bundled.to_owned()));
+        assert!(!commands.iter().any(|c| c.starts_with("git") || c.contains("kustomize")));
+    }
+
+    #[tokio::test]
+    async fn make_bundled_renders_the_picked_components_with_the_bundled_kustomize() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+        let dir = tempfile::tempdir().unwrap();
+
+        KfService { runner, ..Default::default() }
+            .with_components(&["katib".to_owned()])
+            .make_bundled(dir.path()).await.unwrap();
+
+        let commands = recorder.commands();
+        let render = commands.last().unwrap();
+        assert!(commands[1].starts_with("git clone --depth 1 --branch v1.3.0 https://github.com/kubeflow/manifests.git "));
+        assert!(render.starts_with(&format!("sh -c '{}/bin/kustomize build ", dir.path().display())));
+        assert!(render.ends_with(&format!("/manifests/kfpl > {}/manifests/kf.yaml'", dir.path().display())));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn resolve_picks_the_dependencies_of_the_components() {
+        let ids = |components: Vec<&Component>| components.iter().map(|c| c.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(resolve(&["pipelines".to_owned()]).unwrap()), ["istio", "dex", "profiles", "pipelines"]);
+        assert_eq!(ids(resolve(&["kserve".to_owned(), "notebooks".to_owned()]).unwrap()), ["istio", "dex", "notebooks", "profiles", "cert-manager", "kfserving"]);
+    }
+
+    #[test]
+    fn check_components_refuses_other_manifests_releases() {
+        let katib = ["katib".to_owned()];
+
+        assert!(check_components("v1.3.1", &katib).is_ok());
+        assert!(check_components("v1.4.0", &[]).is_ok());
+        assert_eq!(check_components("v1.4.0", &katib).unwrap_err().to_string(), "Picking the KubeFlow components needs the v1.3 manifests (rather than v1.4.0), so please use `--kf-manifests-version v1.3.0`, or deploy every component.");
+    }
+
+    #[test]
+    fn resolve_refuses_an_unknown_component() {
+        let err = resolve(&["spark".to_owned()]).unwrap_err();
+
+        assert_eq!(err.to_string(), "`spark` is not a KubeFlow component (use one of pipelines, central-dashboard, notebooks, katib, kfserving, kserve, tensorboards, volumes-web-app).");
+    }
+
+    #[test]
+    fn without_present_keeps_the_shared_applications() {
+        let kfdef: Value = serde_yaml::from_str(KFDEF).unwrap();
+        let istio = COMPONENTS.iter().find(|c| c.name == "istio").unwrap();
+
+        assert_eq!(applications(&without_present(kfdef, &[istio])), vec!["namespaces", "kubeflow-apps"]);
+    }
+}
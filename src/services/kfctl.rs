@@ -1,72 +1,84 @@
-use tokio::process::Command;
-use anyhow::{Result, Context};
-use async_trait::async_trait;
+use anyhow::{Result, anyhow};
 
-use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
-};
+use crate::services::{download::{Release, ReleaseBinary}, platform::Arch};
 
 static NAME: &str = "kfctl";
+static MINIMUM_VERSION: &str = "v1.1.0";
+/// The full kfctl build (e.g., `v1.1.0-0-g9a3621e`), since the release assets are named after it.
+pub static VERSION: &str = "v1.1.0-0-g9a3621e";
 
-#[derive(Default)]
-pub struct Kfctl {}
+pub struct KfctlRelease;
 
-impl Nameable for Kfctl {
-    fn name(&self) -> &'static str {
+pub type Kfctl = ReleaseBinary<KfctlRelease>;
+
+impl Release for KfctlRelease {
+    fn name() -> &'static str {
         NAME
     }
-}
 
-#[async_trait]
-impl Ensurable for Kfctl {
-    async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
+    fn pin() -> &'static str {
+        VERSION
+    }
+
+    fn minimum_version() -> &'static str {
+        MINIMUM_VERSION
+    }
+
+    fn version_args() -> &'static [&'static str] {
+        &["version"]
     }
 
-    async fn make_present(&self) -> Result<()> {
-        Command::new("curl")
-            .arg("-LO")
-            .arg("https://github.com/kubeflow/kfctl/releases/download/v1.1.0/kfctl_v1.1.0-0-g9a3621e_linux.tar.gz")
-            .status().await
-            .status_to_unit()
-            .context("Unable to curl the kfctl tarball.")?;
-
-        Command::new("tar")
-            .arg("-xvf")
-            .arg("./kfctl_v1.1.0-0-g9a3621e_linux.tar.gz")
-            .status().await
-            .status_to_unit()
-            .context("Unable to untar the kfctl tarball.")?;
-
-        Command::new("rm")
-            .arg("-f")
-            .arg("./kfctl_v1.1.0-0-g9a3621e_linux.tar.gz")
-            .status().await
-            .status_to_unit()
-            .context("Unable to remove the kfctl tarball.")?;
-    
-        Command::new("chmod")
-            .arg("+x")
-            .arg("./kfctl")
-            .status().await
-            .status_to_unit()
-            .context("Unable to change executable permissions on the kfctl binary.")?;
-
-        Command::new("mv")
-            .arg("./kfctl")
-            .arg("/usr/local/bin/kfctl")
-            .status().await
-            .status_to_unit()
-            .context("Unable to copy the kfctl binary (might need sudo).")?;
-
-        Command::new("kfctl")
-            .arg("version")
-            .status().await
-            .status_to_unit()
-            .context("Unable to use kfctl after supposed install.")?;
-
-        Ok(())
+    /// The release is tagged with the version (without the build), and only released for amd64.
+    fn url(version: &str, arch: Arch) -> Result<String> {
+        if arch != Arch::Amd64 {
+            return Err(anyhow!("kfctl is only released for amd64 (rather than {}), so only KFP can be deployed (with `--kfp-only`).", arch));
+        }
+
+        let tag = version.split('-').next().unwrap_or(version);
+
+        Ok(format!("https://github.com/kubeflow/kfctl/releases/download/{}/kfctl_{}_linux.tar.gz", tag, version))
+    }
+
+    fn member() -> Option<&'static str> {
+        Some(NAME)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::services::{command::fake::RecordingRunner, model::Ensurable};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn make_present_installs_the_release_tarball() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kfctl { runner, ..Default::default() }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "download https://github.com/kubeflow/kfctl/releases/download/v1.1.0/kfctl_v1.1.0-0-g9a3621e_linux.tar.gz | extract kfctl | install /usr/local/bin/kfctl",
+            "/usr/local/bin/kfctl version",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_refuses_an_architecture_without_a_release() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        let err = Kfctl { runner, arch: Arch::Arm64, ..Default::default() }.make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "kfctl is only released for amd64 (rather than arm64), so only KFP can be deployed (with `--kfp-only`).");
+        assert!(recorder.commands().is_empty());
+    }
+
+    #[tokio::test]
+    async fn make_present_reports_a_failed_download() {
+        let (runner, recorder) = RecordingRunner::default().fail("download").into_runner();
+
+        let err = Kfctl { runner, ..Default::default() }.make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to install the kfctl binary (might need sudo).");
+        assert_eq!(recorder.commands().len(), 1);
+    }
+}
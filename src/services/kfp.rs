@@ -1,16 +1,81 @@
-use tokio::process::Command;
-use anyhow::{Result, Context};
+use std::path::{Path, PathBuf};
+use anyhow::Result;
 use async_trait::async_trait;
 
 use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
+    services::model::{Nameable, Ensurable, Removable, Inspectable, Upgradable, Trackable, Bundleable},
+    services::version::{Version, parse_version},
+    services::command::{Cmd, Runner},
+    bundle::WHEELS_DIR,
+    helpers::{self, SYSTEM_PREFIX}
 };
 
 static NAME: &str = "kfp";
+// The scripts that the SDK puts into the venv's `bin`, which are linked into the prefix.
+static SCRIPTS: [&str; 2] = ["kfp", "dsl-compile"];
+// Only used when there is no home directory to keep the venv in.
+static SYSTEM_DATA_DIR: &str = "/usr/local/share/kfpl";
+static MINIMUM_VERSION: &str = "v1.0.0";
+pub static VERSION: &str = "1.0.4";
 
-#[derive(Default)]
-pub struct Kfp {}
+pub struct Kfp {
+    version: String,
+    server_version: String,
+    bundle_dir: Option<PathBuf>,
+    prefix: String,
+    venv: String,
+    runner: Runner
+}
+
+impl Default for Kfp {
+    fn default() -> Self {
+        Self {
+            version: VERSION.to_owned(),
+            server_version: VERSION.to_owned(),
+            bundle_dir: None,
+            prefix: SYSTEM_PREFIX.to_owned(),
+            venv: helpers::data_dir().unwrap_or_else(|| SYSTEM_DATA_DIR.into()).join("venv").to_string_lossy().into_owned(),
+            runner: Runner::default()
+        }
+    }
+}
+
+impl Kfp {
+    pub fn with_version(mut self, v: &str) -> Self {
+        self.version = v.to_owned();
+        self
+    }
+
+    /// Sets the version of the KFP backend that the SDK talks to (which `kfp-server-api` has to match).
+    pub fn with_server_version(mut self, v: &str) -> Self {
+        self.server_version = v.to_owned();
+        self
+    }
+
+    /// Installs the SDK out of the wheels in an (unpacked) offline bundle, rather than from PyPI.
+    pub fn with_bundle(mut self, b: Option<&Path>) -> Self {
+        self.bundle_dir = b.map(Path::to_owned);
+        self
+    }
+
+    /// Sets the directory that the `kfp` and `dsl-compile` scripts are linked into.
+    pub fn with_prefix(mut self, p: &str) -> Self {
+        self.prefix = p.to_owned();
+        self
+    }
+
+    fn install_path(&self, script: &str) -> String {
+        format!("{}/{}", self.prefix.trim_end_matches('/'), script)
+    }
+
+    fn venv_path(&self, script: &str) -> String {
+        format!("{}/bin/{}", self.venv.trim_end_matches('/'), script)
+    }
+
+    fn requirements(&self) -> Vec<String> {
+        vec!["urllib3==1.24.2".to_owned(), format!("kfp=={}", self.version), format!("kfp-server-api=={}", self.server_version)]
+    }
+}
 
 impl Nameable for Kfp {
     fn name(&self) -> &'static str {
@@ -20,49 +85,225 @@ impl Nameable for Kfp {
 
 #[async_trait]
 impl Ensurable for Kfp {
+    /// The SDK counts as present when kfpl's venv has it (an SDK installed by other means is left alone).
     async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
+        Cmd::new("test")
+            .arg("-x")
+            .arg(self.venv_path(NAME))
+            .succeeds(&self.runner).await
     }
 
     async fn make_present(&self) -> Result<()> {
-        Command::new("pip3")
+        let offline: Vec<String> = match &self.bundle_dir {
+            Some(dir) => vec!["--no-index".to_owned(), "--find-links".to_owned(), dir.join(WHEELS_DIR).to_string_lossy().into_owned()],
+            None => Vec::new()
+        };
+
+        // The SDK (and its pinned dependencies) get a venv of their own, so that they cannot break other Python projects.
+        Cmd::new("python3")
+            .arg("-m")
+            .arg("venv")
+            .arg(&self.venv)
+            .context("Unable to create the kfp venv (python3 might be missing its `venv` module, e.g., the python3-venv package).")
+            .run(&self.runner).await?;
+
+        Cmd::new(&self.venv_path("pip"))
             .arg("install")
-            .arg("urllib3==1.24.2")
-            .arg("kfp")
-            .arg("kfp-server-api")
+            .args(self.requirements())
             .arg("--upgrade")
-            .arg("--user")
-            .status().await
-            .status_to_unit()
-            .context("Unable to install kfp cli.")?;
-
-        Command::new("sh")
-            .arg("-c")
-            .arg("cp $HOME/.local/bin/kfp /usr/local/bin/kfp")
-            .status().await
-            .status_to_unit()
-            .context("Unable to copy the kfp binary.")?;
-
-        Command::new("sh")
-            .arg("-c")
-            .arg("cp $HOME/.local/bin/dsl-compile /usr/local/bin/dsl-compile")
-            .status().await
-            .status_to_unit()
-            .context("Unable to copy the dsl-compile binary.")?;
-
-        Command::new("which")
-            .arg("dsl-compile")
-            .status().await
-            .status_to_unit()
-            .context("Unable to verify dsl-compile install.")?;
-    
-        Command::new("which")
-            .arg("kfp")
-            .status().await
-            .status_to_unit()
-            .context("Unable to verify kfp install.")?;
+            .args(offline)
+            .context("Unable to install kfp cli.")
+            .run(&self.runner).await?;
+
+        Cmd::new("mkdir")
+            .arg("-p")
+            .arg(&self.prefix)
+            .context("Unable to create the install prefix (might need sudo).")
+            .run(&self.runner).await?;
+
+        // Like `pipx`, the scripts are linked (the venv's scripts run the venv's python, wherever they are called from).
+        for script in &SCRIPTS {
+            Cmd::new("ln")
+                .arg("-sf")
+                .arg(self.venv_path(script))
+                .arg(self.install_path(script))
+                .context(&format!("Unable to link the {} binary (might need sudo).", script))
+                .run(&self.runner).await?;
+
+            Cmd::new("test")
+                .arg("-x")
+                .arg(self.install_path(script))
+                .context(&format!("Unable to verify {} install.", script))
+                .run(&self.runner).await?;
+        }
 
         Ok(())
     }
 }
 
+#[async_trait]
+impl Inspectable for Kfp {
+    async fn version(&self) -> Result<Option<String>> {
+        let output = Cmd::new(&self.venv_path(NAME))
+            .arg("--version")
+            .output(&self.runner).await?;
+
+        Ok(parse_version(&output.stdout).or_else(|| parse_version(&output.stderr)))
+    }
+
+    fn minimum_version(&self) -> Option<Version> {
+        Version::parse(MINIMUM_VERSION)
+    }
+
+    /// A client that differs from the backend produces confusing API errors, rather than a clear one.
+    async fn mismatch(&self) -> Result<Option<String>> {
+        let found = match self.version().await? {
+            Some(f) => f,
+            None => return Ok(None)
+        };
+
+        Ok(match (Version::parse(&found), Version::parse(&self.version)) {
+            (Some(f), Some(pinned)) if f != pinned => Some(format!("the SDK is {}, but KFP {} needs {}", found, self.server_version, self.version)),
+            _ => None
+        })
+    }
+}
+
+impl Upgradable for Kfp {
+    fn pinned_version(&self) -> Option<Version> {
+        Version::parse(&self.version)
+    }
+}
+
+#[async_trait]
+impl Removable for Kfp {
+    async fn make_not_present(&self) -> Result<()> {
+        Cmd::new("rm")
+            .arg("-f")
+            .args(SCRIPTS.iter().map(|s| self.install_path(s)))
+            .context("Unable to remove the kfp and dsl-compile binaries (might need sudo).")
+            .run(&self.runner).await?;
+
+        Cmd::new("rm")
+            .arg("-rf")
+            .arg(&self.venv)
+            .context("Unable to remove the kfp venv.")
+            .run(&self.runner).await
+    }
+}
+
+#[async_trait]
+impl Bundleable for Kfp {
+    async fn make_bundled(&self, dir: &Path) -> Result<()> {
+        Cmd::new("pip3")
+            .arg("download")
+            .args(self.requirements())
+            .arg("-d")
+            .arg(dir.join(WHEELS_DIR).to_string_lossy())
+            .context("Unable to download the kfp cli wheels.")
+            .run(&self.runner).await
+    }
+}
+
+impl Trackable for Kfp {
+    fn installed_version(&self) -> Option<String> {
+        Some(self.version.clone())
+    }
+
+    fn installed_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = SCRIPTS.iter().map(|s| self.install_path(s)).collect();
+        paths.push(self.venv.clone());
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    static VENV: &str = "/home/kfpl/.local/share/kfpl/venv";
+
+    #[tokio::test]
+    async fn make_present_installs_the_sdk_into_the_venv_and_links_the_scripts() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kfp { runner, venv: VENV.to_owned(), ..Default::default() }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "python3 -m venv /home/kfpl/.local/share/kfpl/venv",
+            "/home/kfpl/.local/share/kfpl/venv/bin/pip install urllib3==1.24.2 kfp==1.0.4 kfp-server-api==1.0.4 --upgrade",
+            "mkdir -p /usr/local/bin",
+            "ln -sf /home/kfpl/.local/share/kfpl/venv/bin/kfp /usr/local/bin/kfp",
+            "test -x /usr/local/bin/kfp",
+            "ln -sf /home/kfpl/.local/share/kfpl/venv/bin/dsl-compile /usr/local/bin/dsl-compile",
+            "test -x /usr/local/bin/dsl-compile",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_installs_the_bundled_wheels() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kfp { runner, venv: VENV.to_owned(), ..Default::default() }
+            .with_bundle(Some(Path::new("/tmp/bundle")))
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands()[1], "/home/kfpl/.local/share/kfpl/venv/bin/pip install urllib3==1.24.2 kfp==1.0.4 kfp-server-api==1.0.4 --upgrade --no-index --find-links /tmp/bundle/wheels");
+    }
+
+    #[tokio::test]
+    async fn make_present_pins_the_server_api_to_the_backend() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kfp { runner, venv: VENV.to_owned(), ..Default::default() }
+            .with_version("1.4.1")
+            .with_server_version("1.4.0")
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands()[1], "/home/kfpl/.local/share/kfpl/venv/bin/pip install urllib3==1.24.2 kfp==1.4.1 kfp-server-api==1.4.0 --upgrade");
+    }
+
+    #[tokio::test]
+    async fn mismatch_reports_an_sdk_that_differs_from_the_backend() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("/home/kfpl/.local/share/kfpl/venv/bin/kfp --version", "kfp 1.0.4")
+            .into_runner();
+
+        let kfp = Kfp { runner, venv: VENV.to_owned(), ..Default::default() }
+            .with_version("1.4.0")
+            .with_server_version("1.4.0");
+
+        assert_eq!(kfp.mismatch().await.unwrap().as_deref(), Some("the SDK is 1.0.4, but KFP 1.4.0 needs 1.4.0"));
+    }
+
+    #[tokio::test]
+    async fn mismatch_accepts_the_pinned_sdk() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("/home/kfpl/.local/share/kfpl/venv/bin/kfp --version", "kfp 1.0.4")
+            .into_runner();
+
+        assert_eq!(Kfp { runner, venv: VENV.to_owned(), ..Default::default() }.mismatch().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn is_present_checks_the_venv() {
+        let (runner, recorder) = RecordingRunner::default().respond("test -x", "").into_runner();
+
+        assert!(Kfp { runner, venv: VENV.to_owned(), ..Default::default() }.is_present().await.unwrap());
+        assert_eq!(recorder.commands(), vec!["test -x /home/kfpl/.local/share/kfpl/venv/bin/kfp"]);
+    }
+
+    #[tokio::test]
+    async fn make_not_present_removes_the_scripts_and_the_venv() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kfp { runner, venv: VENV.to_owned(), ..Default::default() }.make_not_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "rm -f /usr/local/bin/kfp /usr/local/bin/dsl-compile",
+            "rm -rf /home/kfpl/.local/share/kfpl/venv",
+        ]);
+    }
+}
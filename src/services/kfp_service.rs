@@ -1,18 +1,28 @@
-use tokio::process::Command;
-use anyhow::{Result, Context, Error};
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+use anyhow::Result;
 use async_trait::async_trait;
+use tokio::time::Duration;
 
 use crate::{
-    services::model::{Nameable, Ensurable},
-    helpers::ExitStatusIntoUnit
+    services::model::{Nameable, Ensurable, Inspectable, Trackable, Bundleable, Readiness, verify_present},
+    services::command::{Cmd, Runner, quote},
+    services::rollout::Rollout,
+    services::kube::{is_deployment_available, deployment_readiness},
+    bundle::MANIFESTS_DIR
 };
 
 static NAME: &str = "KFP Service";
 static SERVICE_NAME: &str = "ml-pipeline";
+static CLUSTER_SCOPED_RESOURCES: &str = "cluster-scoped-resources";
+static PLATFORM_AGNOSTIC: &str = "env/platform-agnostic-pns";
 
 #[derive(Default)]
 pub struct KfpService {
-    kfp_version: String
+    kfp_version: String,
+    k3d_cluster_name: String,
+    bundle_dir: Option<PathBuf>,
+    wait_timeout: Option<Duration>,
+    runner: Runner
 }
 
 impl KfpService {
@@ -20,6 +30,40 @@ impl KfpService {
         self.kfp_version = n.to_owned();
         self
     }
+
+    /// Names the cluster that the deployment is recorded against.
+    pub fn with_k3d_cluster_name(mut self, n: &str) -> Self {
+        self.k3d_cluster_name = n.to_owned();
+        self
+    }
+
+    /// Applies the manifests rendered into an (unpacked) offline bundle, rather than the ones on GitHub.
+    pub fn with_bundle(mut self, b: Option<&Path>) -> Self {
+        self.bundle_dir = b.map(Path::to_owned);
+        self
+    }
+
+    /// Sets how long to wait for the rollout overall (rather than the default of 10 minutes).
+    pub fn with_wait_timeout(mut self, t: Option<Duration>) -> Self {
+        self.wait_timeout = t;
+        self
+    }
+
+    fn kustomization_url(&self, kustomization: &str) -> String {
+        format!("github.com/kubeflow/pipelines/manifests/kustomize/{}?ref={}", kustomization, self.kfp_version)
+    }
+
+    fn manifest_path(dir: &Path, kustomization: &str) -> PathBuf {
+        dir.join(MANIFESTS_DIR).join(format!("kfp-{}.yaml", kustomization.replace('/', "-")))
+    }
+
+    /// Returns the `kubectl apply` arguments for a kustomization (either the GitHub one, or the bundled rendering).
+    fn apply_args(&self, kustomization: &str) -> [String; 2] {
+        match &self.bundle_dir {
+            Some(dir) => ["-f".to_owned(), Self::manifest_path(dir, kustomization).to_string_lossy().into_owned()],
+            None => ["-k".to_owned(), self.kustomization_url(kustomization)]
+        }
+    }
 }
 
 impl Nameable for KfpService {
@@ -31,62 +75,168 @@ impl Nameable for KfpService {
 #[async_trait]
 impl Ensurable for KfpService {
     async fn is_present(&self) -> Result<bool> {
-        let k_out = Command::new("kubectl")
-            .arg("get")
-            .arg("pods")
-            .arg("--all-namespaces")
-            .output().await?.stdout;
-        let k_out_str = std::str::from_utf8(&k_out)?;
-
-        Ok(k_out_str.contains(SERVICE_NAME))
+        is_deployment_available(&self.runner, "kubeflow", SERVICE_NAME).await
     }
 
     async fn make_present(&self) -> Result<()> {
-        Command::new("kubectl")
+        Cmd::new("kubectl")
             .arg("apply")
-            .arg("-k")
-            .arg(format!("github.com/kubeflow/pipelines/manifests/kustomize/cluster-scoped-resources?ref={}", self.kfp_version))
-            .status().await
-            .status_to_unit()
-            .context("Unable to apply the KFP cluster scoped resources.")?;
-
-        Command::new("kubectl")
-            .arg("wait")
-            .arg("--for")
-            .arg("condition=established")
-            .arg("--timeout=60s")
-            .arg("crd/applications.app.k8s.io")
-            .status().await
-            .status_to_unit()
-            .context("Unable to wait for KFP CRD deployment.")?;
-
-        Command::new("kubectl")
+            .args(self.apply_args(CLUSTER_SCOPED_RESOURCES))
+            .context("Unable to apply the KFP cluster scoped resources.")
+            .run(&self.runner).await?;
+
+        Rollout::new()
+            .crd("applications.app.k8s.io")
+            .timeout(self.wait_timeout)
+            .context("Unable to wait for KFP CRD deployment.")
+            .run(&self.runner).await?;
+
+        Cmd::new("kubectl")
             .arg("apply")
-            .arg("-k")
-            .arg(format!("github.com/kubeflow/pipelines/manifests/kustomize/env/platform-agnostic-pns?ref={}", self.kfp_version))
-            .status().await
-            .status_to_unit()
-            .context("Unable to apply the KFP platform agnostic deployment.")?;
+            .args(self.apply_args(PLATFORM_AGNOSTIC))
+            .context("Unable to apply the KFP platform agnostic deployment.")
+            .run(&self.runner).await?;
 
-        tokio::time::delay_for(tokio::time::Duration::from_secs(10)).await;
+        println!("Waiting for the KFP deployments to come up ...");
 
-        println!("Waiting for the ml-pipeline deployment to complete ...");
+        Rollout::new()
+            .deployment("kubeflow", SERVICE_NAME)
+            .deployments("kubeflow")
+            .timeout(self.wait_timeout)
+            .context("Unable to wait for the KFP deployments to come up.")
+            .run(&self.runner).await?;
 
-        Command::new("kubectl")
-            .arg("wait")
-            .arg("--for=condition=available")
-            .arg("--timeout=600s")
-            .arg("deploy/ml-pipeline")
+        verify_present(self, "Unable to verify that the kfp service is running.").await
+    }
+}
+
+#[async_trait]
+impl Inspectable for KfpService {
+    async fn version(&self) -> Result<Option<String>> {
+        let image = Cmd::new("kubectl")
+            .arg("get")
+            .arg(format!("deploy/{}", SERVICE_NAME))
             .arg("-n")
             .arg("kubeflow")
-            .status().await
-            .status_to_unit()
-            .context("Unable to wait for the ml-pipeline deployment to come up.")?;
-
-        if self.is_present().await? {
-            Ok(())
-        } else {
-            Err(Error::msg("Unable to verify that the kfp service is running."))
+            .arg("-o")
+            .arg("jsonpath={.spec.template.spec.containers[0].image}")
+            .output(&self.runner).await?.stdout;
+
+        // The api server image is tagged with the KFP version (e.g., `gcr.io/ml-pipeline/api-server:1.0.4`).
+        Ok(image.rsplit_once(':').map(|(_, tag)| tag.trim().to_owned()).filter(|t| !t.is_empty()))
+    }
+
+    async fn readiness(&self) -> Result<Option<Readiness>> {
+        deployment_readiness(&self.runner, "kubeflow").await
+    }
+}
+
+#[async_trait]
+impl Bundleable for KfpService {
+    async fn make_bundled(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir.join(MANIFESTS_DIR))?;
+
+        for kustomization in &[CLUSTER_SCOPED_RESOURCES, PLATFORM_AGNOSTIC] {
+            Cmd::new("sh")
+                .arg("-c")
+                .arg(format!("kubectl kustomize {} > {}", quote(&self.kustomization_url(kustomization)), quote(&Self::manifest_path(dir, kustomization).to_string_lossy())))
+                .context(&format!("Unable to render the KFP `{}` manifests.", kustomization))
+                .run(&self.runner).await?;
         }
+
+        Ok(())
+    }
+}
+
+impl Trackable for KfpService {
+    fn state_key(&self) -> String {
+        format!("{} ({})", NAME, self.k3d_cluster_name)
+    }
+
+    fn installed_version(&self) -> Option<String> {
+        Some(self.kfp_version.clone())
+    }
+
+    fn install_options(&self) -> BTreeMap<String, String> {
+        let mut options = BTreeMap::new();
+        options.insert("k3d_cluster_name".to_owned(), self.k3d_cluster_name.clone());
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    /// Reports the CRDs as established, and the deployments as available.
+    fn rolled_out() -> RecordingRunner {
+        RecordingRunner::default()
+            .respond("kubectl get crd/", "True")
+            .respond("kubectl get deploy/ml-pipeline", "True")
+            .respond("kubectl get deploy -n kubeflow", "minio=True\nml-pipeline=True\n")
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn make_present_applies_the_versioned_manifests() {
+        let (runner, recorder) = rolled_out()
+            .respond("GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline", r#"{"status": {"conditions": [{"type": "Available", "status": "True"}]}}"#)
+            .into_runner();
+
+        KfpService { runner, ..Default::default() }
+            .with_kfp_version("1.0.4")
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "kubectl apply -k 'github.com/kubeflow/pipelines/manifests/kustomize/cluster-scoped-resources?ref=1.0.4'",
+            r#"kubectl get crd/applications.app.k8s.io -o 'jsonpath={.status.conditions[?(@.type=="Established")].status}'"#,
+            "kubectl apply -k 'github.com/kubeflow/pipelines/manifests/kustomize/env/platform-agnostic-pns?ref=1.0.4'",
+            r#"kubectl get deploy/ml-pipeline -n kubeflow -o 'jsonpath={.status.conditions[?(@.type=="Available")].status}'"#,
+            r#"kubectl get deploy -n kubeflow -o 'jsonpath={range .items[*]}{.metadata.name}={.status.conditions[?(@.type=="Available")].status}{"\n"}{end}'"#,
+            "GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_applies_the_bundled_manifests() {
+        let (runner, recorder) = rolled_out()
+            .respond("GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline", r#"{"status": {"conditions": [{"type": "Available", "status": "True"}]}}"#)
+            .into_runner();
+
+        KfpService { runner, ..Default::default() }
+            .with_kfp_version("1.0.4")
+            .with_bundle(Some(Path::new("/tmp/bundle")))
+            .make_present().await.unwrap();
+
+        let commands = recorder.commands();
+        assert_eq!(commands[0], "kubectl apply -f /tmp/bundle/manifests/kfp-cluster-scoped-resources.yaml");
+        assert_eq!(commands[2], "kubectl apply -f /tmp/bundle/manifests/kfp-env-platform-agnostic-pns.yaml");
+        assert_eq!(commands.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn make_present_reports_a_failed_platform_apply() {
+        let (runner, recorder) = rolled_out()
+            .fail("kubectl apply -k 'github.com/kubeflow/pipelines/manifests/kustomize/env")
+            .into_runner();
+
+        let err = KfpService { runner, ..Default::default() }
+            .with_kfp_version("1.0.4")
+            .make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "Unable to apply the KFP platform agnostic deployment.");
+        assert_eq!(recorder.commands().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn version_comes_from_the_api_server_image() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("kubectl get deploy/ml-pipeline", "gcr.io/ml-pipeline/api-server:1.0.4")
+            .into_runner();
+
+        let version = KfpService { runner, ..Default::default() }.version().await.unwrap();
+
+        assert_eq!(version.as_deref(), Some("1.0.4"));
+    }
+}
@@ -0,0 +1,163 @@
+use std::{collections::BTreeMap, path::Path};
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::services::{
+    model::{Nameable, Ensurable, Removable, Inspectable, Trackable, ClusterProvider, Readiness, verify_present, node_version, node_readiness},
+    command::{Cmd, Runner}
+};
+
+static NAME: &str = "kind cluster";
+
+#[derive(Default)]
+pub struct KindService {
+    cluster_name: String,
+    runner: Runner,
+}
+
+impl KindService {
+    pub fn with_cluster_name(mut self, n: &str) -> Self {
+        self.cluster_name = n.to_owned();
+        self
+    }
+}
+
+impl Nameable for KindService {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+#[async_trait]
+impl Ensurable for KindService {
+    async fn is_present(&self) -> Result<bool> {
+        let kind_out = Cmd::new("kind")
+            .arg("get")
+            .arg("clusters")
+            .output(&self.runner).await?.stdout;
+
+        Ok(kind_out.lines().any(|l| l.trim() == self.cluster_name))
+    }
+
+    async fn make_present(&self) -> Result<()> {
+        // kind switches the kubeconfig to the new cluster on its own.
+        Cmd::new("kind")
+            .arg("create")
+            .arg("cluster")
+            .args(["--name", &self.cluster_name])
+            .args(["--wait", "600s"])
+            .context("Unable to start the kind k8s cluster (is kind installed?).")
+            .run(&self.runner).await?;
+
+        verify_present(self, "Unable to verify that the kind cluster is running.").await
+    }
+}
+
+#[async_trait]
+impl Removable for KindService {
+    async fn make_not_present(&self) -> Result<()> {
+        Cmd::new("kind")
+            .arg("delete")
+            .arg("cluster")
+            .args(["--name", &self.cluster_name])
+            .context("Unable to stop the kind k8s cluster.")
+            .run(&self.runner).await
+    }
+}
+
+#[async_trait]
+impl Inspectable for KindService {
+    async fn version(&self) -> Result<Option<String>> {
+        node_version(&self.runner, &self.context()).await
+    }
+
+    async fn readiness(&self) -> Result<Option<Readiness>> {
+        node_readiness(&self.runner, &self.context()).await
+    }
+}
+
+#[async_trait]
+impl ClusterProvider for KindService {
+    fn context(&self) -> String {
+        format!("kind-{}", self.cluster_name)
+    }
+
+    async fn export_kubeconfig(&self) -> Result<()> {
+        Cmd::new("kind")
+            .arg("export")
+            .arg("kubeconfig")
+            .args(["--name", &self.cluster_name])
+            .context("Unable to export the kubeconfig of the kind cluster.")
+            .run(&self.runner).await
+    }
+
+    async fn import_images(&self, archive: &Path) -> Result<()> {
+        Cmd::new("kind")
+            .arg("load")
+            .arg("image-archive")
+            .arg(archive.to_string_lossy())
+            .args(["--name", &self.cluster_name])
+            .context("Unable to import the images into the kind cluster.")
+            .run(&self.runner).await
+    }
+}
+
+impl Trackable for KindService {
+    fn state_key(&self) -> String {
+        format!("{} ({})", NAME, self.cluster_name)
+    }
+
+    fn install_options(&self) -> BTreeMap<String, String> {
+        let mut options = BTreeMap::new();
+        options.insert("k3d_cluster_name".to_owned(), self.cluster_name.clone());
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    fn kind_service(runner: Runner) -> KindService {
+        KindService { runner, ..Default::default() }.with_cluster_name("kfp-local")
+    }
+
+    #[tokio::test]
+    async fn make_present_creates_the_cluster_and_waits_for_it() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("kind get clusters", "kind\nkfp-local\n")
+            .into_runner();
+
+        kind_service(runner).make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "kind create cluster --name kfp-local --wait 600s",
+            "kind get clusters",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn is_present_matches_the_whole_cluster_name() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("kind get clusters", "kfp-local-2\n")
+            .into_runner();
+
+        assert!(!kind_service(runner).is_present().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn export_and_import_target_the_cluster() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+        let service = kind_service(runner);
+
+        service.export_kubeconfig().await.unwrap();
+        service.import_images(Path::new("/tmp/images.tar")).await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "kind export kubeconfig --name kfp-local",
+            "kind load image-archive /tmp/images.tar --name kfp-local",
+        ]);
+    }
+}
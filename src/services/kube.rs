@@ -0,0 +1,411 @@
+use std::{env, fmt, fs, io, path::{Path, PathBuf}, sync::Arc};
+use anyhow::{Result, Context, anyhow};
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    services::command::{Output, Runner},
+    services::model::Readiness,
+    helpers
+};
+
+static DEFAULT_KUBECONFIG: &str = "~/.kube/config";
+
+// Requests.
+
+/// A description of a read-only request to the Kubernetes API (of the kubeconfig's current context), which is made
+/// in-process, rather than by scraping `kubectl` output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiGet {
+    path: String,
+    context: String,
+}
+
+impl ApiGet {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            context: format!("Unable to request `{}` from the Kubernetes API.", path),
+        }
+    }
+
+    /// Sets the error message reported when the request cannot be made.
+    pub fn context(mut self, c: &str) -> Self {
+        self.context = c.to_owned();
+        self
+    }
+
+    /// Makes the request (even during a dry run), and captures the response body (a missing resource, or a missing
+    /// kubeconfig, is reported as an unsuccessful output, as a failed `kubectl` query would be, while a kubeconfig that
+    /// cannot be used, or a cluster that cannot be asked, is an error).
+    pub async fn output(self, runner: &Runner) -> Result<Output> {
+        runner.get(&self).await.context(self.context)
+    }
+
+    /// Makes the request (blocking).
+    pub(crate) fn fetch(&self) -> Result<Output> {
+        let client = match Client::load()? {
+            Some(client) => client,
+            None => return Ok(Output { success: false, stdout: String::new(), stderr: "There is no kubeconfig.".to_owned() })
+        };
+
+        let mut request = client.agent.get(&format!("{}{}", client.server.trim_end_matches('/'), self.path));
+        if let Some(token) = &client.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+
+        to_output(request.call())
+    }
+}
+
+/// Captures a response, where only a 404 means that the resource is absent (anything else that failed, such as a
+/// refused credential, says nothing about the resource).
+fn to_output(result: Result<ureq::Response, ureq::Error>) -> Result<Output> {
+    match result {
+        Ok(response) => Ok(Output { success: true, stdout: response.into_string()?, stderr: String::new() }),
+        Err(ureq::Error::Status(404, response)) => Ok(Output { success: false, stdout: String::new(), stderr: response.into_string().unwrap_or_default() }),
+        Err(ureq::Error::Status(code, response)) => Err(anyhow!("The Kubernetes API responded with {}: {}", code, response.into_string().unwrap_or_default().trim())),
+        Err(e) => Err(anyhow!("Unable to reach the Kubernetes API: {}", e))
+    }
+}
+
+impl fmt::Display for ApiGet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GET {}", self.path)
+    }
+}
+
+// Deployments.
+
+fn deployment_path(namespace: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("/apis/apps/v1/namespaces/{}/deployments/{}", namespace, name),
+        None => format!("/apis/apps/v1/namespaces/{}/deployments", namespace)
+    }
+}
+
+/// Returns whether the `Available` condition of a deployment is `True` (so crashed, or terminating, pods do not count).
+fn is_available(deployment: &Value) -> bool {
+    deployment["status"]["conditions"].as_array()
+        .map(|conditions| conditions.iter().any(|c| c["type"] == "Available" && c["status"] == "True"))
+        .unwrap_or(false)
+}
+
+/// Returns whether a deployment exists, and is available.
+pub(crate) async fn is_deployment_available(runner: &Runner, namespace: &str, name: &str) -> Result<bool> {
+    let output = ApiGet::new(&deployment_path(namespace, Some(name)))
+        .context(&format!("Unable to look up the `{}` deployment.", name))
+        .output(runner).await?;
+
+    if !output.success {
+        return Ok(false);
+    }
+
+    let deployment: Value = serde_json::from_str(&output.stdout).with_context(|| format!("Unable to parse the `{}` deployment.", name))?;
+
+    Ok(is_available(&deployment))
+}
+
+/// Counts the available deployments in a namespace.
+pub(crate) async fn deployment_readiness(runner: &Runner, namespace: &str) -> Result<Option<Readiness>> {
+    let output = ApiGet::new(&deployment_path(namespace, None))
+        .context(&format!("Unable to look up the deployments in `{}`.", namespace))
+        .output(runner).await?;
+
+    if !output.success {
+        return Ok(None);
+    }
+
+    let list: Value = serde_json::from_str(&output.stdout).with_context(|| format!("Unable to parse the deployments in `{}`.", namespace))?;
+    let deployments = list["items"].as_array().map(Vec::as_slice).unwrap_or_default();
+
+    if deployments.is_empty() {
+        return Ok(None);
+    }
+
+    let ready = deployments.iter().filter(|d| is_available(d)).count();
+
+    Ok(Some(Readiness { kind: "deployments", ready, total: deployments.len() }))
+}
+
+// Kubeconfig.
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Kubeconfig {
+    current_context: Option<String>,
+    contexts: Vec<NamedContext>,
+    clusters: Vec<NamedCluster>,
+    users: Vec<NamedUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedContext {
+    name: String,
+    context: ContextEntry,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContextEntry {
+    cluster: String,
+    user: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedCluster {
+    name: String,
+    cluster: ClusterEntry,
+    /// The directory of the kubeconfig that defines the cluster (which its credential files are relative to).
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct ClusterEntry {
+    server: String,
+    certificate_authority: Option<String>,
+    certificate_authority_data: Option<String>,
+    insecure_skip_tls_verify: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedUser {
+    name: String,
+    user: UserEntry,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct UserEntry {
+    client_certificate: Option<String>,
+    client_certificate_data: Option<String>,
+    client_key: Option<String>,
+    client_key_data: Option<String>,
+    token: Option<String>,
+    exec: Option<serde_yaml::Value>,
+    auth_provider: Option<serde_yaml::Value>,
+}
+
+impl Kubeconfig {
+    /// Adds the entries of a later kubeconfig file, as `kubectl` merges them: the first file to set the current
+    /// context wins, as does the first definition of each context, cluster, and user (lookups take the first match).
+    fn merge(&mut self, mut other: Kubeconfig, dir: &Path) {
+        if self.current_context.as_deref().map(str::is_empty).unwrap_or(true) {
+            self.current_context = other.current_context;
+        }
+
+        other.clusters.iter_mut().for_each(|c| c.dir = dir.to_owned());
+        other.users.iter_mut().for_each(|u| u.dir = dir.to_owned());
+
+        self.contexts.extend(other.contexts);
+        self.clusters.extend(other.clusters);
+        self.users.extend(other.users);
+    }
+
+    /// Returns the cluster and the user of the current context.
+    fn current(&self) -> Result<(&NamedCluster, &NamedUser)> {
+        let name = self.current_context.as_deref().filter(|c| !c.is_empty()).ok_or_else(|| anyhow!("The kubeconfig has no current context."))?;
+        let context = &self.contexts.iter().find(|c| c.name == name).ok_or_else(|| anyhow!("The kubeconfig has no `{}` context.", name))?.context;
+        let cluster = self.clusters.iter().find(|c| c.name == context.cluster).ok_or_else(|| anyhow!("The kubeconfig has no `{}` cluster.", context.cluster))?;
+        let user = self.users.iter().find(|u| u.name == context.user).ok_or_else(|| anyhow!("The kubeconfig has no `{}` user.", context.user))?;
+
+        Ok((cluster, user))
+    }
+
+    /// Reads and merges the kubeconfig files that `kubectl` uses (every file in `KUBECONFIG`, or `~/.kube/config`),
+    /// skipping the missing ones, as `kubectl` does.  Returns `None` when there are none.
+    fn load() -> Result<Option<Self>> {
+        let paths: Vec<String> = match env::var("KUBECONFIG") {
+            Ok(paths) if !paths.is_empty() => paths.split(':').filter(|p| !p.is_empty()).map(str::to_owned).collect(),
+            _ => vec![DEFAULT_KUBECONFIG.to_owned()]
+        };
+
+        let mut merged: Option<Kubeconfig> = None;
+
+        for path in paths.iter().map(|p| PathBuf::from(helpers::expand_home(p))) {
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("Unable to read the kubeconfig `{}`.", path.display()))
+            };
+
+            let kubeconfig: Kubeconfig = serde_yaml::from_str(&text).with_context(|| format!("Unable to parse the kubeconfig `{}`.", path.display()))?;
+            let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+            merged.get_or_insert_with(Kubeconfig::default).merge(kubeconfig, dir);
+        }
+
+        Ok(merged)
+    }
+}
+
+/// Reads a PEM credential, either inline (base64) or from a file (relative to the kubeconfig).
+fn pem(data: &Option<String>, file: &Option<String>, dir: &Path) -> Result<Option<Vec<u8>>> {
+    if let Some(data) = data {
+        return Ok(Some(base64::engine::general_purpose::STANDARD.decode(data.trim()).context("Unable to decode a kubeconfig credential.")?));
+    }
+
+    file.as_ref()
+        .map(|f| fs::read(dir.join(f)).with_context(|| format!("Unable to read the kubeconfig credential `{}`.", f)))
+        .transpose()
+}
+
+// Client.
+
+/// A connection to the API server of the kubeconfig's current context (client certificates and tokens are supported).
+struct Client {
+    agent: ureq::Agent,
+    server: String,
+    token: Option<String>,
+}
+
+impl Client {
+    /// Connects to the current context (or returns `None` when there is no kubeconfig at all).
+    fn load() -> Result<Option<Self>> {
+        let kubeconfig = match Kubeconfig::load()? {
+            Some(kubeconfig) => kubeconfig,
+            None => return Ok(None)
+        };
+
+        let (NamedCluster { cluster, dir: cluster_dir, .. }, NamedUser { user, dir: user_dir, .. }) = kubeconfig.current()?;
+
+        if cluster.insecure_skip_tls_verify {
+            return Err(anyhow!("kfpl does not support `insecure-skip-tls-verify` clusters."));
+        }
+
+        if user.exec.is_some() || user.auth_provider.is_some() {
+            return Err(anyhow!("kfpl does not support `exec` or `auth-provider` kubeconfig credentials (only client certificates and tokens)."));
+        }
+
+        let mut roots = rustls::RootCertStore::empty();
+        let ca = pem(&cluster.certificate_authority_data, &cluster.certificate_authority, cluster_dir)?.ok_or_else(|| anyhow!("The kubeconfig cluster has no certificate authority."))?;
+        for cert in rustls_pemfile::certs(&mut io::Cursor::new(ca)) {
+            roots.add(cert?)?;
+        }
+
+        let builder = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots);
+
+        let certificate = pem(&user.client_certificate_data, &user.client_certificate, user_dir)?;
+        let key = pem(&user.client_key_data, &user.client_key, user_dir)?;
+
+        let tls_config = match (certificate, key) {
+            (Some(certificate), Some(key)) => {
+                let certificates = rustls_pemfile::certs(&mut io::Cursor::new(certificate)).collect::<Result<Vec<_>, _>>()?;
+                let key = rustls_pemfile::private_key(&mut io::Cursor::new(key))?.ok_or_else(|| anyhow!("The kubeconfig user has no private key."))?;
+
+                builder.with_client_auth_cert(certificates, key)?
+            },
+            _ => builder.with_no_client_auth()
+        };
+
+        Ok(Some(Self {
+            agent: ureq::AgentBuilder::new().tls_config(Arc::new(tls_config)).build(),
+            server: cluster.server.clone(),
+            token: user.token.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    static KUBECONFIG: &str = r#"
+apiVersion: v1
+kind: Config
+current-context: k3d-kfp-local
+contexts:
+- name: kind-kfp-local
+  context:
+    cluster: kind-kfp-local
+    user: kind-kfp-local
+- name: k3d-kfp-local
+  context:
+    cluster: k3d-kfp-local
+    user: admin@k3d-kfp-local
+clusters:
+- name: k3d-kfp-local
+  cluster:
+    server: https://0.0.0.0:6443
+    certificate-authority-data: Y2E=
+users:
+- name: admin@k3d-kfp-local
+  user:
+    client-certificate-data: Y2VydA==
+    client-key-data: a2V5
+"#;
+
+    #[test]
+    fn kubeconfig_resolves_the_current_context() {
+        let kubeconfig: Kubeconfig = serde_yaml::from_str(KUBECONFIG).unwrap();
+
+        let (cluster, user) = kubeconfig.current().unwrap();
+
+        assert_eq!(cluster.cluster.server, "https://0.0.0.0:6443");
+        assert_eq!(pem(&user.user.client_key_data, &user.user.client_key, Path::new("/")).unwrap(), Some(b"key".to_vec()));
+    }
+
+    #[test]
+    fn kubeconfig_merges_the_later_files() {
+        let mut kubeconfig = Kubeconfig::default();
+        kubeconfig.merge(serde_yaml::from_str("current-context: kind-kfp-local\nclusters:\n- name: kind-kfp-local\n  cluster:\n    server: https://127.0.0.1:1234\n").unwrap(), Path::new("/home/kfpl/.kube"));
+        kubeconfig.merge(serde_yaml::from_str(&KUBECONFIG.replace("- name: admin@k3d-kfp-local", "- name: kind-kfp-local")).unwrap(), Path::new("/etc/kube"));
+
+        let (cluster, user) = kubeconfig.current().unwrap();
+
+        assert_eq!((cluster.cluster.server.as_str(), cluster.dir.as_path()), ("https://127.0.0.1:1234", Path::new("/home/kfpl/.kube")));
+        assert_eq!(user.dir, Path::new("/etc/kube"));
+    }
+
+    #[test]
+    fn only_a_missing_resource_is_an_unsuccessful_output() {
+        let status = |code: u16| ureq::Error::Status(code, ureq::Response::new(code, "status", "denied").unwrap());
+
+        assert!(!to_output(Err(status(404))).unwrap().success);
+        assert_eq!(to_output(Err(status(403))).unwrap_err().to_string(), "The Kubernetes API responded with 403: denied");
+        assert!(to_output(Ok(ureq::Response::new(200, "OK", "{}").unwrap())).unwrap().success);
+    }
+
+    #[test]
+    fn kubeconfig_reports_a_dangling_context() {
+        let kubeconfig: Kubeconfig = serde_yaml::from_str(&KUBECONFIG.replace("current-context: k3d-kfp-local", "current-context: kind-kfp-local")).unwrap();
+
+        assert_eq!(kubeconfig.current().unwrap_err().to_string(), "The kubeconfig has no `kind-kfp-local` cluster.");
+    }
+
+    #[tokio::test]
+    async fn deployment_is_available_only_with_the_condition() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("GET /apis/apps/v1/namespaces/kubeflow/deployments/ml-pipeline", r#"{"status": {"conditions": [{"type": "Available", "status": "True"}]}}"#)
+            .respond("GET /apis/apps/v1/namespaces/kubeflow/deployments/minio", r#"{"status": {"conditions": [{"type": "Available", "status": "False"}]}}"#)
+            .into_runner();
+
+        assert!(is_deployment_available(&runner, "kubeflow", "ml-pipeline").await.unwrap());
+        assert!(!is_deployment_available(&runner, "kubeflow", "minio").await.unwrap());
+        assert!(!is_deployment_available(&runner, "kubeflow", "mysql").await.unwrap());
+        assert_eq!(recorder.commands()[2], "GET /apis/apps/v1/namespaces/kubeflow/deployments/mysql");
+    }
+
+    #[tokio::test]
+    async fn deployment_readiness_counts_the_available_deployments() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("GET /apis/apps/v1/namespaces/kubeflow/deployments", r#"{"items": [
+                {"status": {"conditions": [{"type": "Available", "status": "True"}]}},
+                {"status": {"conditions": [{"type": "Progressing", "status": "True"}]}},
+                {"status": {}}
+            ]}"#)
+            .into_runner();
+
+        let readiness = deployment_readiness(&runner, "kubeflow").await.unwrap().unwrap();
+
+        assert_eq!((readiness.ready, readiness.total, readiness.kind), (1, 3, "deployments"));
+    }
+}
@@ -1,60 +1,130 @@
-use tokio::process::Command;
-use anyhow::{Result, Context};
-use async_trait::async_trait;
+use anyhow::Result;
 
-use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
-};
+use crate::services::{download::{Release, ReleaseBinary}, platform::Arch};
 
 static NAME: &str = "kubectl";
-static VERSION: &str = "v1.19.2";
+static MINIMUM_VERSION: &str = "v1.19.0";
+pub static VERSION: &str = "v1.19.2";
 
-#[derive(Default)]
-pub struct Kubectl {}
+pub struct KubectlRelease;
 
-impl Nameable for Kubectl {
-    fn name(&self) -> &'static str {
+pub type Kubectl = ReleaseBinary<KubectlRelease>;
+
+impl Release for KubectlRelease {
+    fn name() -> &'static str {
         NAME
     }
-}
 
-#[async_trait]
-impl Ensurable for Kubectl {
-    async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
-    }
-
-    async fn make_present(&self) -> Result<()> {
-        Command::new("curl")
-            .arg("-LO")
-            .arg(format!("https://storage.googleapis.com/kubernetes-release/release/{}/bin/linux/amd64/kubectl", VERSION))
-            .status().await
-            .status_to_unit()
-            .context("Unable to curl the kubectl binary.")?;
-    
-        Command::new("chmod")
-            .arg("+x")
-            .arg("./kubectl")
-            .status().await
-            .status_to_unit()
-            .context("Unable to change executable permissions on the kubectl binary.")?;
-
-        Command::new("mv")
-            .arg("./kubectl")
-            .arg("/usr/local/bin/kubectl")
-            .status().await
-            .status_to_unit()
-            .context("Unable to copy the kubectl binary (might need sudo).")?;
-
-        Command::new("kubectl")
-            .arg("version")
-            .arg("--client")
-            .status().await
-            .status_to_unit()
-            .context("Unable to use kubectl after supposed install.")?;
-
-        Ok(())
+    fn pin() -> &'static str {
+        VERSION
+    }
+
+    fn minimum_version() -> &'static str {
+        MINIMUM_VERSION
+    }
+
+    fn version_args() -> &'static [&'static str] {
+        &["version", "--client"]
+    }
+
+    fn url(version: &str, arch: Arch) -> Result<String> {
+        Ok(format!("https://storage.googleapis.com/kubernetes-release/release/{}/bin/linux/{}/kubectl", version, arch))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::services::{command::fake::RecordingRunner, model::{Ensurable, Inspectable, UpgradableEntity}};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn make_present_installs_the_pinned_binary() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kubectl { runner, ..Default::default() }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "download https://storage.googleapis.com/kubernetes-release/release/v1.19.2/bin/linux/amd64/kubectl | install /usr/local/bin/kubectl",
+            "/usr/local/bin/kubectl version --client",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_installs_into_the_prefix() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kubectl { runner, ..Default::default() }
+            .with_prefix("/home/kfpl/.local/bin/")
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "download https://storage.googleapis.com/kubernetes-release/release/v1.19.2/bin/linux/amd64/kubectl | install /home/kfpl/.local/bin/kubectl",
+            "/home/kfpl/.local/bin/kubectl version --client",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_verifies_the_download_against_the_checksum() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+        let sha256 = "a".repeat(64);
+
+        Kubectl { runner, ..Default::default() }
+            .with_sha256(Some(&sha256))
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands()[0], format!("download https://storage.googleapis.com/kubernetes-release/release/v1.19.2/bin/linux/amd64/kubectl | verify sha256 {} | install /usr/local/bin/kubectl", sha256));
+    }
+
+    #[tokio::test]
+    async fn make_present_refuses_a_malformed_checksum_before_downloading() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        let err = Kubectl { runner, ..Default::default() }
+            .with_sha256(Some("not-a-checksum"))
+            .make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "The SHA-256 checksum `not-a-checksum` for `kubectl` v1.19.2 is not 64 hex digits.");
+        assert!(recorder.commands().is_empty());
+    }
+
+    #[tokio::test]
+    async fn version_is_parsed_from_the_client_output() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("/usr/local/bin/kubectl version", r#"Client Version: version.Info{Major:"1", Minor:"19", GitVersion:"v1.19.2"}"#)
+            .into_runner();
+
+        assert_eq!(Kubectl { runner, ..Default::default() }.version().await.unwrap().as_deref(), Some("v1.19.2"));
+    }
+
+    #[tokio::test]
+    async fn upgrade_reinstalls_a_binary_that_differs_from_the_pin() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("test -x /usr/local/bin/kubectl", "")
+            .respond("/usr/local/bin/kubectl version", "Client Version: v1.18.0")
+            .into_runner();
+
+        Kubectl { runner, ..Default::default() }
+            .with_version("v1.20.0")
+            .upgrade(false).await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "test -x /usr/local/bin/kubectl",
+            "/usr/local/bin/kubectl version --client",
+            "download https://storage.googleapis.com/kubernetes-release/release/v1.20.0/bin/linux/amd64/kubectl | install /usr/local/bin/kubectl",
+            "/usr/local/bin/kubectl version --client",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn upgrade_skips_a_binary_at_the_pin() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("test -x /usr/local/bin/kubectl", "")
+            .respond("/usr/local/bin/kubectl version", "Client Version: v1.19.2")
+            .into_runner();
+
+        Kubectl { runner, ..Default::default() }.upgrade(false).await.unwrap();
+
+        assert_eq!(recorder.commands(), vec!["test -x /usr/local/bin/kubectl", "/usr/local/bin/kubectl version --client"]);
+    }
+}
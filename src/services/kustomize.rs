@@ -0,0 +1,62 @@
+use anyhow::Result;
+
+use crate::services::{download::{Release, ReleaseBinary}, platform::Arch};
+
+static NAME: &str = "kustomize";
+static MINIMUM_VERSION: &str = "v3.2.0";
+// The KubeFlow manifests (v1.3) only build with kustomize v3.2.0.
+pub static VERSION: &str = "v3.2.0";
+
+pub struct KustomizeRelease;
+
+pub type Kustomize = ReleaseBinary<KustomizeRelease>;
+
+impl Release for KustomizeRelease {
+    fn name() -> &'static str {
+        NAME
+    }
+
+    fn pin() -> &'static str {
+        VERSION
+    }
+
+    fn minimum_version() -> &'static str {
+        MINIMUM_VERSION
+    }
+
+    fn version_args() -> &'static [&'static str] {
+        &["version"]
+    }
+
+    fn url(version: &str, arch: Arch) -> Result<String> {
+        Ok(format!("https://github.com/kubernetes-sigs/kustomize/releases/download/{}/kustomize_{}_linux_{}", version, version.trim_start_matches('v'), arch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::{command::fake::RecordingRunner, model::{Ensurable, Inspectable}};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn make_present_installs_the_release_binary() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        Kustomize { runner, ..Default::default() }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "download https://github.com/kubernetes-sigs/kustomize/releases/download/v3.2.0/kustomize_3.2.0_linux_amd64 | install /usr/local/bin/kustomize",
+            "/usr/local/bin/kustomize version",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn version_is_parsed_from_the_build_info() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("/usr/local/bin/kustomize version", "Version: {KustomizeVersion:3.2.0 GitCommit:a3103f1e62ddb5b696daa3fd359bb6f2e8333b49 BuildDate:2019-09-18T16:26:36Z GoOs:linux GoArch:amd64}")
+            .into_runner();
+
+        assert_eq!(Kustomize { runner, ..Default::default() }.version().await.unwrap().as_deref(), Some("3.2.0"));
+    }
+}
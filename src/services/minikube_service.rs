@@ -0,0 +1,151 @@
+use std::{collections::BTreeMap, path::Path};
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::services::{
+    model::{Nameable, Ensurable, Removable, Inspectable, Trackable, ClusterProvider, Readiness, verify_present, node_version, node_readiness},
+    command::{Cmd, Runner}
+};
+
+static NAME: &str = "minikube cluster";
+
+/// A minikube cluster, which is a minikube profile (named after the cluster).
+#[derive(Default)]
+pub struct MinikubeService {
+    cluster_name: String,
+    runner: Runner,
+}
+
+impl MinikubeService {
+    pub fn with_cluster_name(mut self, n: &str) -> Self {
+        self.cluster_name = n.to_owned();
+        self
+    }
+}
+
+impl Nameable for MinikubeService {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+#[async_trait]
+impl Ensurable for MinikubeService {
+    async fn is_present(&self) -> Result<bool> {
+        // `minikube status` fails for a stopped (or missing) profile.
+        Cmd::new("minikube")
+            .arg("status")
+            .args(["-p", &self.cluster_name])
+            .succeeds(&self.runner).await
+    }
+
+    async fn make_present(&self) -> Result<()> {
+        // minikube switches the kubeconfig to the new cluster on its own.
+        Cmd::new("minikube")
+            .arg("start")
+            .args(["-p", &self.cluster_name])
+            .arg("--wait=all")
+            .context("Unable to start the minikube k8s cluster (is minikube installed?).")
+            .run(&self.runner).await?;
+
+        verify_present(self, "Unable to verify that the minikube cluster is running.").await
+    }
+}
+
+#[async_trait]
+impl Removable for MinikubeService {
+    async fn make_not_present(&self) -> Result<()> {
+        Cmd::new("minikube")
+            .arg("delete")
+            .args(["-p", &self.cluster_name])
+            .context("Unable to stop the minikube k8s cluster.")
+            .run(&self.runner).await
+    }
+}
+
+#[async_trait]
+impl Inspectable for MinikubeService {
+    async fn version(&self) -> Result<Option<String>> {
+        node_version(&self.runner, &self.context()).await
+    }
+
+    async fn readiness(&self) -> Result<Option<Readiness>> {
+        node_readiness(&self.runner, &self.context()).await
+    }
+}
+
+#[async_trait]
+impl ClusterProvider for MinikubeService {
+    fn context(&self) -> String {
+        self.cluster_name.clone()
+    }
+
+    async fn export_kubeconfig(&self) -> Result<()> {
+        Cmd::new("minikube")
+            .arg("update-context")
+            .args(["-p", &self.cluster_name])
+            .context("Unable to export the kubeconfig of the minikube cluster.")
+            .run(&self.runner).await
+    }
+
+    async fn import_images(&self, archive: &Path) -> Result<()> {
+        Cmd::new("minikube")
+            .arg("image")
+            .arg("load")
+            .arg(archive.to_string_lossy())
+            .args(["-p", &self.cluster_name])
+            .context("Unable to import the images into the minikube cluster.")
+            .run(&self.runner).await
+    }
+}
+
+impl Trackable for MinikubeService {
+    fn state_key(&self) -> String {
+        format!("{} ({})", NAME, self.cluster_name)
+    }
+
+    fn install_options(&self) -> BTreeMap<String, String> {
+        let mut options = BTreeMap::new();
+        options.insert("k3d_cluster_name".to_owned(), self.cluster_name.clone());
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    fn minikube_service(runner: Runner) -> MinikubeService {
+        MinikubeService { runner, ..Default::default() }.with_cluster_name("kfp-local")
+    }
+
+    #[tokio::test]
+    async fn make_present_starts_the_profile() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("minikube status", "kfp-local\ntype: Control Plane\nhost: Running\n")
+            .into_runner();
+
+        minikube_service(runner).make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "minikube start -p kfp-local --wait=all",
+            "minikube status -p kfp-local",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn export_and_import_target_the_profile() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+        let service = minikube_service(runner);
+
+        service.export_kubeconfig().await.unwrap();
+        service.import_images(Path::new("/tmp/images.tar")).await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "minikube update-context -p kfp-local",
+            "minikube image load /tmp/images.tar -p kfp-local",
+        ]);
+    }
+}
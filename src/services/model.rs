@@ -1,9 +1,14 @@
-use anyhow::Result;
-use tokio::process::Command;
-use std::process::Stdio;
+use std::{collections::BTreeMap, path::Path};
+use anyhow::{Result, Error};
 use async_trait::async_trait;
 use yansi::Paint;
 use dialoguer::Confirm;
+use serde::Serialize;
+
+use crate::{
+    services::{command::{Cmd, Runner, is_dry_run}, version::{Version, parse_version}},
+    state::{self, State, Install}
+};
 
 static TAB: &str = "  ";
 
@@ -24,6 +29,118 @@ pub trait Removable {
     async fn make_not_present(&self) -> Result<()>;
 }
 
+#[async_trait]
+pub trait Inspectable {
+    async fn version(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// The oldest version that kfpl works with (older installs are reported as outdated).
+    fn minimum_version(&self) -> Option<Version> {
+        None
+    }
+
+    async fn readiness(&self) -> Result<Option<Readiness>> {
+        Ok(None)
+    }
+
+    /// Describes how a present install does not fit what it works with (e.g., an SDK that does not match the deployed
+    /// backend), which `ensure` offers to fix by reinstalling.
+    async fn mismatch(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Describes what an absent install lacks (e.g., the components of a partial deployment), which `ensure` reports
+    /// alongside its offer to make it present.
+    async fn missing(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[async_trait]
+pub trait Upgradable: Ensurable {
+    /// The version that kfpl installs (and upgrades, or downgrades, to).
+    fn pinned_version(&self) -> Option<Version>;
+
+    async fn make_upgraded(&self) -> Result<()> {
+        // Installing over the top of an existing install is an upgrade for most entities.
+        self.make_present().await
+    }
+}
+
+#[async_trait]
+pub trait Bundleable {
+    /// Writes what `make_present` needs (when offline) into the bundle directory.
+    async fn make_bundled(&self, dir: &Path) -> Result<()>;
+}
+
+/// A local kubernetes cluster that the deployments go into (created by `make_present`, and deleted by
+/// `make_not_present`).
+#[async_trait]
+pub trait ClusterProvider: Ensurable + Removable + Inspectable + Trackable {
+    /// The `kubectl` context of the cluster.
+    fn context(&self) -> String;
+
+    /// Merges the cluster into the kubeconfig, and makes it the current context.
+    async fn export_kubeconfig(&self) -> Result<()>;
+
+    /// Loads the images in a `docker save` archive into the nodes of the cluster.
+    async fn import_images(&self, archive: &Path) -> Result<()>;
+}
+
+pub trait Trackable: Nameable {
+    /// The key that the entity is recorded under in the state file (deployments are recorded per cluster).
+    fn state_key(&self) -> String {
+        self.name().to_owned()
+    }
+
+    /// The version that `make_present` installs (or deploys).
+    fn installed_version(&self) -> Option<String> {
+        None
+    }
+
+    /// The files that `make_present` writes (recorded, so that kfpl knows what it installed).
+    fn installed_paths(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The options that `make_present` uses (recorded, so that later runs can tell when they differ).
+    fn install_options(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    /// Describes what `make_present` would record (without a timestamp).
+    fn install(&self) -> Install {
+        Install {
+            version: self.installed_version(),
+            paths: self.installed_paths(),
+            options: self.install_options(),
+            ..Default::default()
+        }
+    }
+}
+
+// Reporting types.
+
+#[derive(Serialize)]
+pub struct Readiness {
+    pub kind: &'static str,
+    pub ready: usize,
+    pub total: usize,
+}
+
+#[derive(Serialize)]
+pub struct Status {
+    pub name: &'static str,
+    pub present: bool,
+    pub version: Option<String>,
+    pub minimum_version: Option<String>,
+    pub outdated: bool,
+    pub readiness: Option<Readiness>,
+    /// What kfpl recorded when it made the entity present (if it did).
+    pub install: Option<Install>,
+}
+
 // "Public" Entity definitions (performs passthrough to helper functions).
 
 #[async_trait]
@@ -33,20 +150,65 @@ pub trait EnsurableEntity {
 
 #[async_trait]
 impl<T> EnsurableEntity for T
-    where T: Nameable + Ensurable + Send + Sync
+    where T: ?Sized + Nameable + Ensurable + Inspectable + Trackable + Send + Sync
 {
     async fn ensure(&self, confirm: bool) -> Result<()> {
         let name = self.name();
         print!("Checking if `{}` is present ... ", Paint::blue(name));
 
         if self.is_present().await? {
-            println!("💯!");
+            let (problem, reason, fix) = match (outdated_version(self).await, self.mismatch().await?) {
+                (Some((found, minimum)), _) => ("outdated", format!("{} is older than {}", found, minimum), "upgrade"),
+                (None, Some(mismatch)) => ("mismatched", mismatch, "reinstall"),
+                (None, None) => {
+                    match recorded_difference(self) {
+                        Some(recorded) => println!("💯 ({})!", Paint::yellow(format!("but kfpl already made it present with {}, so the requested settings are not applied", recorded))),
+                        None => println!("💯!")
+                    }
+
+                    return Ok(())
+                }
+            };
+
+            println!("{} ({})!", Paint::yellow(problem), reason);
+
+            if is_dry_run() {
+                println!("{}Planning upgrade of `{}` ...", TAB, Paint::blue(name));
+                return self.make_present().await;
+            }
+
+            if confirm && !Confirm::new().with_prompt(format!("{}`{}` is {}: do you want me to {} it?", TAB, Paint::blue(name), problem, fix)).interact()? {
+                println!("{}Skipping ...", TAB);
+                return Ok(())
+            }
+
+            println!("{}Upgrading `{}` ({}) ...", TAB, Paint::blue(name), Paint::yellow("you may need to interact with the execution"));
+
+            self.make_present().await?;
+            record_install(self)?;
+
+            println!("{}Successfully upgraded `{}`.", TAB, Paint::blue(name));
+
             return Ok(())
         }
 
-        println!("{}!", Paint::red("✘"));
+        let missing = match self.missing().await? {
+            Some(missing) => {
+                println!("{} ({})!", Paint::red("✘"), missing);
+                format!(" ({})", missing)
+            },
+            None => {
+                println!("{}!", Paint::red("✘"));
+                String::new()
+            }
+        };
+
+        if is_dry_run() {
+            println!("{}Planning presence of `{}` ...", TAB, Paint::blue(name));
+            return self.make_present().await;
+        }
         
-        if confirm && !Confirm::new().with_prompt(format!("{}`{}` is not present: do you want me to make it so?", TAB, Paint::blue(name))).interact()? {
+        if confirm && !Confirm::new().with_prompt(format!("{}`{}` is not present{}: do you want me to make it so?", TAB, Paint::blue(name), missing)).interact()? {
             println!("{}Skipping ...", TAB);
             return Ok(())
         }
@@ -54,6 +216,7 @@ impl<T> EnsurableEntity for T
         println!("{}Ensuring presence of `{}` ({}) ...", TAB, Paint::blue(name), Paint::yellow("you may need to interact with the execution"));
         
         self.make_present().await?;
+        record_install(self)?;
         
         println!("{}Successfully ensured `{}`.", TAB, Paint::blue(name));
         
@@ -68,16 +231,21 @@ pub trait RemovableEntity {
 
 #[async_trait]
 impl<T> RemovableEntity for T
-    where T: Nameable + Ensurable + Removable + Send + Sync
+    where T: ?Sized + Nameable + Ensurable + Removable + Trackable + Send + Sync
 {
     async fn remove(&self, confirm: bool) -> Result<()> {
         let name = self.name();
 
         if !self.is_present().await? {
-            println!("{} `{}` is not running!", TAB, Paint::blue(name));
+            println!("{} `{}` is not present!", TAB, Paint::blue(name));
             return Ok(())
         }
 
+        if is_dry_run() {
+            println!("{}Planning removal of `{}` ...", TAB, Paint::blue(name));
+            return self.make_not_present().await;
+        }
+
         if confirm && !Confirm::new().with_prompt(format!("{}`{}` is present: do you want me to remove it?", TAB, Paint::blue(name))).interact()? {
             println!("{}Skipping ...", TAB);
             return Ok(())
@@ -86,6 +254,7 @@ impl<T> RemovableEntity for T
         println!("{}Removing presence of `{}` ({}) ...", TAB, Paint::blue(name), Paint::yellow("you may need to interact with the execution [and sudo]"));
         
         self.make_not_present().await?;
+        forget_install(self)?;
         
         println!("{}Successfully removed `{}`.", TAB, Paint::blue(name));
         
@@ -93,15 +262,222 @@ impl<T> RemovableEntity for T
     }
 }
 
+#[async_trait]
+pub trait UpgradableEntity {
+    async fn upgrade(&self, confirm: bool) -> Result<()>;
+}
+
+#[async_trait]
+impl<T> UpgradableEntity for T
+    where T: Nameable + Ensurable + Inspectable + Upgradable + Trackable + Send + Sync
+{
+    async fn upgrade(&self, confirm: bool) -> Result<()> {
+        let name = self.name();
+        print!("Checking if `{}` is at the pinned version ... ", Paint::blue(name));
+
+        if !self.is_present().await? {
+            println!("{} (check out `kfpl init`).", Paint::red("not present"));
+            return Ok(())
+        }
+
+        let pinned = match self.pinned_version() {
+            Some(p) => p,
+            None => {
+                println!("{} (no pinned version).", Paint::yellow("skipping"));
+                return Ok(())
+            }
+        };
+
+        let found = self.version().await.unwrap_or(None).as_deref().and_then(Version::parse);
+
+        if found == Some(pinned) {
+            println!("💯!");
+            return Ok(())
+        }
+
+        let found = found.map(|f| f.to_string()).unwrap_or_else(|| "an unknown version".to_owned());
+        println!("{} ({} is installed, {} is pinned)!", Paint::red("✘"), found, pinned);
+
+        if is_dry_run() {
+            println!("{}Planning upgrade of `{}` ...", TAB, Paint::blue(name));
+            return self.make_upgraded().await;
+        }
+
+        if confirm && !Confirm::new().with_prompt(format!("{}`{}` differs from the pinned version: do you want me to reinstall it at {}?", TAB, Paint::blue(name), pinned)).interact()? {
+            println!("{}Skipping ...", TAB);
+            return Ok(())
+        }
+
+        println!("{}Reinstalling `{}` at {} ({}) ...", TAB, Paint::blue(name), pinned, Paint::yellow("you may need to interact with the execution"));
+
+        self.make_upgraded().await?;
+        record_install(self)?;
+
+        println!("{}Successfully upgraded `{}`.", TAB, Paint::blue(name));
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait BundleableEntity {
+    async fn bundle(&self, dir: &Path) -> Result<()>;
+}
+
+#[async_trait]
+impl<T> BundleableEntity for T
+    where T: Nameable + Bundleable + Send + Sync
+{
+    async fn bundle(&self, dir: &Path) -> Result<()> {
+        let name = self.name();
+
+        if is_dry_run() {
+            println!("Planning the bundling of `{}` ...", Paint::blue(name));
+            return self.make_bundled(dir).await;
+        }
+
+        println!("Bundling `{}` ...", Paint::blue(name));
+
+        self.make_bundled(dir).await?;
+
+        println!("{}Successfully bundled `{}`.", TAB, Paint::blue(name));
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait InspectableEntity {
+    async fn status(&self) -> Status;
+}
+
+#[async_trait]
+impl<T> InspectableEntity for T
+    where T: ?Sized + Nameable + Ensurable + Inspectable + Trackable + Send + Sync
+{
+    async fn status(&self) -> Status {
+        // Failures (e.g., a missing binary, or an unreachable cluster) are reported as absence, rather than errors.
+        let present = self.is_present().await.unwrap_or(false);
+        let version = if present { self.version().await.unwrap_or(None) } else { None };
+        let readiness = if present { self.readiness().await.unwrap_or(None) } else { None };
+
+        let minimum = self.minimum_version();
+        let outdated = match (version.as_deref().and_then(Version::parse), minimum) {
+            (Some(found), Some(minimum)) => !found.satisfies(&minimum),
+            _ => false
+        };
+
+        Status {
+            name: self.name(),
+            present,
+            version,
+            minimum_version: minimum.map(|m| m.to_string()),
+            outdated,
+            readiness,
+            install: State::load().ok().and_then(|mut state| state.installs.remove(&self.state_key()))
+        }
+    }
+}
+
 // Other helper methods.
 
-pub(crate) async fn is_binary_present<T>(s: &T) -> Result<bool>
+pub(crate) async fn is_binary_present<T>(s: &T, runner: &Runner) -> Result<bool>
     where T: Nameable
 {
-    Ok(Command::new("which")
+    Cmd::new("which")
         .arg(s.name())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .stdin(Stdio::null())
-        .status().await?.success())
-}
\ No newline at end of file
+        .succeeds(runner).await
+}
+
+/// Records that kfpl made the entity present (nothing is recorded during a dry run).
+pub(crate) fn record_install<T>(s: &T) -> Result<()>
+    where T: ?Sized + Trackable
+{
+    if is_dry_run() {
+        return Ok(());
+    }
+
+    let install = Install { installed_at: state::now(), ..s.install() };
+
+    State::update(|state| { state.installs.insert(s.state_key(), install); })
+}
+
+/// Forgets that kfpl made the entity present (nothing is forgotten during a dry run).
+pub(crate) fn forget_install<T>(s: &T) -> Result<()>
+    where T: ?Sized + Trackable
+{
+    if is_dry_run() {
+        return Ok(());
+    }
+
+    State::update(|state| { state.installs.remove(&s.state_key()); })
+}
+
+/// Describes how the recorded install of a present entity differs from the requested one (e.g., "you already deployed
+/// KFP 1.0.4 on this cluster"), if kfpl recorded it at all.
+pub(crate) fn recorded_difference<T>(s: &T) -> Option<String>
+    where T: ?Sized + Trackable
+{
+    let recorded = State::load().ok()?.installs.remove(&s.state_key())?;
+
+    recorded.difference(&s.install())
+}
+
+/// Returns the detected and minimum versions of a present entity, if the detected version is too old.
+pub(crate) async fn outdated_version<T>(s: &T) -> Option<(Version, Version)>
+    where T: ?Sized + Inspectable + Sync
+{
+    let minimum = s.minimum_version()?;
+
+    // An undetectable version is given the benefit of the doubt.
+    let found = s.version().await.unwrap_or(None).as_deref().and_then(Version::parse)?;
+
+    if found.satisfies(&minimum) { None } else { Some((found, minimum)) }
+}
+
+/// Verifies that a step sequence actually made the entity present (trivially true during a dry run, since nothing ran).
+pub(crate) async fn verify_present<T>(s: &T, message: &'static str) -> Result<()>
+    where T: ?Sized + Ensurable + Sync
+{
+    if is_dry_run() || s.is_present().await? {
+        Ok(())
+    } else {
+        Err(Error::msg(message))
+    }
+}
+
+pub(crate) async fn binary_version<T>(s: &T, runner: &Runner, args: &[&str]) -> Result<Option<String>>
+    where T: Nameable
+{
+    let output = Cmd::new(s.name())
+        .args(args.iter().copied())
+        .output(runner).await?;
+
+    Ok(parse_version(&output.stdout).or_else(|| parse_version(&output.stderr)))
+}
+
+/// Returns the columns (name, status, roles, age, version) of every node in the cluster of a context.
+async fn nodes(runner: &Runner, context: &str) -> Result<Vec<Vec<String>>> {
+    let k_out = Cmd::new("kubectl")
+        .arg("get")
+        .arg("nodes")
+        .arg("--no-headers")
+        .args(["--context", context])
+        .output(runner).await?.stdout;
+
+    Ok(k_out.lines().map(|l| l.split_whitespace().map(str::to_owned).collect()).collect())
+}
+
+/// Returns the kubernetes version of the (first) node in the cluster of a context.
+pub(crate) async fn node_version(runner: &Runner, context: &str) -> Result<Option<String>> {
+    Ok(nodes(runner, context).await?.first().and_then(|c| c.get(4)).map(|v| v.to_string()))
+}
+
+/// Counts the ready nodes in the cluster of a context.
+pub(crate) async fn node_readiness(runner: &Runner, context: &str) -> Result<Option<Readiness>> {
+    let nodes = nodes(runner, context).await?;
+    let ready = nodes.iter().filter(|c| c.get(1).map(String::as_str) == Some("Ready")).count();
+
+    Ok(Some(Readiness { kind: "nodes", ready, total: nodes.len() }))
+}
+
@@ -1,16 +1,45 @@
-use tokio::process::Command;
-use anyhow::{Result, Context};
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 
 use crate::{
-    services::model::{Nameable, Ensurable, is_binary_present},
-    helpers::ExitStatusIntoUnit
+    services::model::{Nameable, Ensurable, Removable, Inspectable, Trackable, is_binary_present, binary_version},
+    services::command::{Cmd, Runner},
+    services::platform::{Distro, PackageManager}
 };
 
 static NAME: &str = "pip3";
 
-#[derive(Default)]
-pub struct Pip3 {}
+pub struct Pip3 {
+    distro: Distro,
+    runner: Runner
+}
+
+impl Default for Pip3 {
+    fn default() -> Self {
+        Self {
+            distro: Distro::current(),
+            runner: Runner::default()
+        }
+    }
+}
+
+/// Returns the pip package, which the distributions name differently.
+fn pip_package(manager: PackageManager) -> &'static str {
+    match manager {
+        PackageManager::Apk => "py3-pip",
+        PackageManager::Pacman => "python-pip",
+        _ => "python3-pip"
+    }
+}
+
+/// Returns python3 and pip, along with the `venv` module (which Debian and Ubuntu package separately).
+fn packages(manager: PackageManager) -> Vec<&'static str> {
+    match manager {
+        PackageManager::Apt => vec!["python3", pip_package(manager), "python3-venv"],
+        PackageManager::Pacman => vec!["python", pip_package(manager)],
+        _ => vec!["python3", pip_package(manager)]
+    }
+}
 
 impl Nameable for Pip3 {
     fn name(&self) -> &'static str {
@@ -21,32 +50,75 @@ impl Nameable for Pip3 {
 #[async_trait]
 impl Ensurable for Pip3 {
     async fn is_present(&self) -> Result<bool> {
-        is_binary_present(self).await
+        is_binary_present(self, &self.runner).await
     }
 
     async fn make_present(&self) -> Result<()> {
-        Command::new("apt-get")
-            .arg("update")
-            .status().await
-            .status_to_unit()
-            .context("Unable to update apt-get.")?;
-    
-        Command::new("apt-get")
-            .arg("-y")
-            .arg("install")
-            .arg("python3")
-            .arg("python3-pip")
-            .status().await
-            .status_to_unit()
-            .context("Unable to install pip3 via apt-get.  You can install python3 and pip3 manually, and try again.")?;
-
-        Command::new("pip3")
+        let manager = self.distro.package_manager()
+            .map_err(|e| anyhow!("{}  You can install python3 and pip3 manually, and try again.", e))?;
+
+        for cmd in manager.install(&packages(manager)) {
+            cmd
+                .context(&format!("Unable to install pip3 via {}.  You can install python3 and pip3 manually, and try again.", manager))
+                .run(&self.runner).await?;
+        }
+
+        Cmd::new("pip3")
             .arg("--version")
-            .status().await
-            .status_to_unit()
-            .context("Unable to verify pip3 installation.")?;
+            .context("Unable to verify pip3 installation.")
+            .run(&self.runner).await?;
 
         Ok(())
     }
 }
 
+#[async_trait]
+impl Inspectable for Pip3 {
+    async fn version(&self) -> Result<Option<String>> {
+        binary_version(self, &self.runner, &["--version"]).await
+    }
+}
+
+#[async_trait]
+impl Removable for Pip3 {
+    async fn make_not_present(&self) -> Result<()> {
+        let manager = self.distro.package_manager()?;
+
+        manager.remove(&[pip_package(manager)])
+            .context(&format!("Unable to remove pip3 via {} (might need sudo).", manager))
+            .run(&self.runner).await
+    }
+}
+
+impl Trackable for Pip3 {}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn make_present_uses_the_package_manager_of_the_distro() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+        let distro = Distro::parse("ID=alpine\nVERSION_ID=3.13.0\nPRETTY_NAME=\"Alpine Linux v3.13\"");
+
+        Pip3 { distro, runner }.make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "apk add --no-cache python3 py3-pip",
+            "pip3 --version",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_present_refuses_an_unknown_distro() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+        let distro = Distro::parse("ID=nixos\nPRETTY_NAME=\"NixOS 20.09\"");
+
+        let err = Pip3 { distro, runner }.make_present().await.unwrap_err();
+
+        assert_eq!(err.to_string(), "kfpl does not know the package manager of NixOS 20.09 (it supports apt, dnf, yum, apk, pacman, and zypper).  You can install python3 and pip3 manually, and try again.");
+        assert!(recorder.commands().is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+use anyhow::{Result, anyhow};
+
+use crate::services::command::Cmd;
+
+/// The CPU architecture, as the release artifacts name it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arch {
+    Amd64,
+    Arm64,
+    Unsupported(&'static str),
+}
+
+impl Arch {
+    /// Parses an architecture as `uname -m` (or `std::env::consts::ARCH`) names it.
+    pub fn parse(machine: &'static str) -> Self {
+        match machine {
+            "x86_64" | "amd64" => Arch::Amd64,
+            "aarch64" | "arm64" => Arch::Arm64,
+            other => Arch::Unsupported(other),
+        }
+    }
+
+    #[cfg(not(test))]
+    pub fn current() -> Self {
+        Self::parse(std::env::consts::ARCH)
+    }
+
+    /// Keeps the tests on the amd64 artifacts, whatever the host is.
+    #[cfg(test)]
+    pub fn current() -> Self {
+        Arch::Amd64
+    }
+
+    /// Returns the architecture, or an error when there are no artifacts for it.
+    pub fn supported(self) -> Result<Self> {
+        match self {
+            Arch::Unsupported(a) => Err(anyhow!("kfpl does not support the `{}` architecture (only amd64 and arm64).", a)),
+            a => Ok(a),
+        }
+    }
+}
+
+impl Default for Arch {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arch::Amd64 => write!(f, "amd64"),
+            Arch::Arm64 => write!(f, "arm64"),
+            Arch::Unsupported(a) => write!(f, "{}", a),
+        }
+    }
+}
+
+/// The Linux distribution, as `/etc/os-release` describes it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distro {
+    pub id: String,
+    pub id_like: Vec<String>,
+    pub version_id: String,
+    pub name: String,
+}
+
+impl Distro {
+    pub fn parse(os_release: &str) -> Self {
+        let mut distro = Distro::default();
+
+        for line in os_release.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim().trim_matches(|c| c == '"' || c == '\'')),
+                None => continue
+            };
+
+            match key {
+                "ID" => distro.id = value.to_owned(),
+                "ID_LIKE" => distro.id_like = value.split_whitespace().map(str::to_owned).collect(),
+                "VERSION_ID" => distro.version_id = value.to_owned(),
+                "PRETTY_NAME" => distro.name = value.to_owned(),
+                _ => {}
+            }
+        }
+
+        if distro.name.is_empty() {
+            distro.name = distro.id.clone();
+        }
+
+        distro
+    }
+
+    #[cfg(not(test))]
+    pub fn current() -> Self {
+        // `/usr/lib/os-release` is the fallback that the spec allows for.
+        ["/etc/os-release", "/usr/lib/os-release"].iter()
+            .find_map(|p| std::fs::read_to_string(p).ok())
+            .map(|s| Self::parse(&s))
+            .unwrap_or_else(|| Distro { name: "an unknown distribution".to_owned(), ..Default::default() })
+    }
+
+    /// Keeps the tests on `apt-get`, whatever the host is.
+    #[cfg(test)]
+    pub fn current() -> Self {
+        Self::parse("ID=ubuntu\nID_LIKE=debian\nVERSION_ID=\"20.04\"\nPRETTY_NAME=\"Ubuntu 20.04 LTS\"")
+    }
+
+    /// Picks the package manager from the distribution (or the ones it is like).
+    pub fn package_manager(&self) -> Result<PackageManager> {
+        let major: u32 = self.version_id.split('.').next().and_then(|v| v.parse().ok()).unwrap_or(0);
+
+        for id in std::iter::once(&self.id).chain(&self.id_like) {
+            let manager = match id.as_str() {
+                "debian" | "ubuntu" => PackageManager::Apt,
+                "fedora" => PackageManager::Dnf,
+                // RHEL 8 (and its rebuilds) moved to `dnf`, as did Amazon Linux 2023.
+                "rhel" | "centos" | "amzn" if major >= 8 => PackageManager::Dnf,
+                "rhel" | "centos" | "amzn" => PackageManager::Yum,
+                "alpine" => PackageManager::Apk,
+                "arch" => PackageManager::Pacman,
+                "suse" | "opensuse" | "sles" => PackageManager::Zypper,
+                _ => continue
+            };
+
+            return Ok(manager);
+        }
+
+        Err(anyhow!("kfpl does not know the package manager of {} (it supports apt, dnf, yum, apk, pacman, and zypper).", self.name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Yum,
+    Apk,
+    Pacman,
+    Zypper,
+}
+
+impl PackageManager {
+    /// Returns the commands that install the packages (`apt-get` needs its index refreshed first).
+    pub fn install(self, packages: &[&str]) -> Vec<Cmd> {
+        let install = match self {
+            PackageManager::Apt => Cmd::new("apt-get").arg("-y").arg("install"),
+            PackageManager::Dnf => Cmd::new("dnf").arg("-y").arg("install"),
+            PackageManager::Yum => Cmd::new("yum").arg("-y").arg("install"),
+            PackageManager::Apk => Cmd::new("apk").arg("add").arg("--no-cache"),
+            PackageManager::Pacman => Cmd::new("pacman").arg("-S").arg("--needed").arg("--noconfirm"),
+            PackageManager::Zypper => Cmd::new("zypper").arg("--non-interactive").arg("install"),
+        };
+
+        match self {
+            PackageManager::Apt => vec![Cmd::new("apt-get").arg("update"), install.args(packages.iter().copied())],
+            _ => vec![install.args(packages.iter().copied())]
+        }
+    }
+
+    pub fn remove(self, packages: &[&str]) -> Cmd {
+        let remove = match self {
+            PackageManager::Apt => Cmd::new("apt-get").arg("-y").arg("remove"),
+            PackageManager::Dnf => Cmd::new("dnf").arg("-y").arg("remove"),
+            PackageManager::Yum => Cmd::new("yum").arg("-y").arg("remove"),
+            PackageManager::Apk => Cmd::new("apk").arg("del"),
+            PackageManager::Pacman => Cmd::new("pacman").arg("-R").arg("--noconfirm"),
+            PackageManager::Zypper => Cmd::new("zypper").arg("--non-interactive").arg("remove"),
+        };
+
+        remove.args(packages.iter().copied())
+    }
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PackageManager::Apt => "apt-get",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Yum => "yum",
+            PackageManager::Apk => "apk",
+            PackageManager::Pacman => "pacman",
+            PackageManager::Zypper => "zypper",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_manager(os_release: &str) -> Option<PackageManager> {
+        Distro::parse(os_release).package_manager().ok()
+    }
+
+    #[test]
+    fn arch_is_parsed_from_the_machine_name() {
+        assert_eq!(Arch::parse("x86_64"), Arch::Amd64);
+        assert_eq!(Arch::parse("aarch64"), Arch::Arm64);
+        assert_eq!(Arch::parse("arm64").to_string(), "arm64");
+    }
+
+    #[test]
+    fn unsupported_arch_is_an_error() {
+        let err = Arch::parse("riscv64").supported().unwrap_err();
+
+        assert_eq!(err.to_string(), "kfpl does not support the `riscv64` architecture (only amd64 and arm64).");
+    }
+
+    #[test]
+    fn distro_is_parsed_from_os_release() {
+        let distro = Distro::parse("NAME=\"Rocky Linux\"\nID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\nVERSION_ID=\"8.5\"\nPRETTY_NAME=\"Rocky Linux 8.5 (Green Obsidian)\"\n");
+
+        assert_eq!(distro, Distro {
+            id: "rocky".to_owned(),
+            id_like: vec!["rhel".to_owned(), "centos".to_owned(), "fedora".to_owned()],
+            version_id: "8.5".to_owned(),
+            name: "Rocky Linux 8.5 (Green Obsidian)".to_owned(),
+        });
+    }
+
+    #[test]
+    fn package_manager_follows_the_distro_family() {
+        assert_eq!(package_manager("ID=pop\nID_LIKE=\"ubuntu debian\""), Some(PackageManager::Apt));
+        assert_eq!(package_manager("ID=centos\nVERSION_ID=\"7\""), Some(PackageManager::Yum));
+        assert_eq!(package_manager("ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\nVERSION_ID=\"8.5\""), Some(PackageManager::Dnf));
+        assert_eq!(package_manager("ID=alpine\nVERSION_ID=3.13.0"), Some(PackageManager::Apk));
+        assert_eq!(package_manager("ID=manjaro\nID_LIKE=arch"), Some(PackageManager::Pacman));
+        assert_eq!(package_manager("ID=\"opensuse-leap\"\nID_LIKE=\"suse opensuse\""), Some(PackageManager::Zypper));
+    }
+
+    #[test]
+    fn unknown_distro_is_an_error() {
+        let err = Distro::parse("ID=nixos\nPRETTY_NAME=\"NixOS 20.09\"").package_manager().unwrap_err();
+
+        assert_eq!(err.to_string(), "kfpl does not know the package manager of NixOS 20.09 (it supports apt, dnf, yum, apk, pacman, and zypper).");
+    }
+}
@@ -1,10 +1,9 @@
-use tokio::process::Command;
 use anyhow::{Result, Context};
 use async_trait::async_trait;
 
 use crate::{
-    services::model::{Nameable, Ensurable},
-    helpers::ExitStatusIntoUnit
+    services::model::{Nameable, Ensurable, Inspectable, Trackable},
+    services::command::{Cmd, Runner}
 };
 
 static NAME: &str = "Port Forward";
@@ -13,7 +12,8 @@ static NAME: &str = "Port Forward";
 pub struct PortForward {
     kfp_only: bool,
     port: String,
-    address: String
+    address: String,
+    runner: Runner
 }
 
 impl PortForward {
@@ -31,6 +31,15 @@ impl PortForward {
         self.address = a.to_owned();
         self
     }
+
+    /// Returns the namespace and the service that the port is forwarded to.
+    fn backend(&self) -> (&'static str, &'static str) {
+        if self.kfp_only {
+            ("kubeflow", "ml-pipeline-ui")
+        } else {
+            ("istio-system", "istio-ingressgateway")
+        }
+    }
 }
 
 impl Nameable for PortForward {
@@ -42,40 +51,85 @@ impl Nameable for PortForward {
 #[async_trait]
 impl Ensurable for PortForward {
     async fn is_present(&self) -> Result<bool> {
-        // TODO: This is a hack: eventually, check to see if the port is already open.
-        Ok(false)
+        let port = self.port.parse::<u16>().context("The port forward port must be a number.")?;
+
+        // Any process could listen on the port, so this looks for the `kubectl port-forward` itself.
+        Cmd::new("pgrep")
+            .arg("-f")
+            .arg(format!("kubectl port-forward .*svc/{} {}:80", self.backend().1, port))
+            .succeeds(&self.runner).await
     }
 
     async fn make_present(&self) -> Result<()> {
-        
-        if self.kfp_only {
-            Command::new("kubectl")
-                .arg("port-forward")
-                .arg("--address")
-                .arg(&self.address)
-                .arg("-n")
-                .arg("kubeflow")
-                .arg("svc/ml-pipeline-ui")
-                .arg(format!("{}:80", self.port))
-                .status().await
-                .status_to_unit()
-                .context("Unable to start the port-forward.")?;
-        } else {
-            Command::new("kubectl")
-                .arg("port-forward")
-                .arg("--address")
-                .arg(&self.address)
-                .arg("-n")
-                .arg("istio-system")
-                .arg("svc/istio-ingressgateway")
-                .arg(format!("{}:80", self.port))
-                .status().await
-                .status_to_unit()
-                .context("Unable to start the port-forward.")?;
-        }
-        
+        let (namespace, service) = self.backend();
+
+        Cmd::new("kubectl")
+            .arg("port-forward")
+            .arg("--address")
+            .arg(&self.address)
+            .arg("-n")
+            .arg(namespace)
+            .arg(format!("svc/{}", service))
+            .arg(format!("{}:80", self.port))
+            .context("Unable to start the port-forward.")
+            .run(&self.runner).await?;
+
         // This is a blocking call...
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl Inspectable for PortForward {}
+
+impl Trackable for PortForward {}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn make_present_forwards_the_istio_gateway() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        PortForward { runner, ..Default::default() }
+            .with_port("8080")
+            .with_address("127.0.0.1")
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec!["kubectl port-forward --address 127.0.0.1 -n istio-system svc/istio-ingressgateway 8080:80"]);
+    }
+
+    #[tokio::test]
+    async fn make_present_forwards_the_kfp_ui() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        PortForward { runner, ..Default::default() }
+            .with_kfp_only(true)
+            .with_port("8080")
+            .with_address("127.0.0.1")
+            .make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec!["kubectl port-forward --address 127.0.0.1 -n kubeflow svc/ml-pipeline-ui 8080:80"]);
+    }
+
+    #[tokio::test]
+    async fn is_present_looks_for_the_port_forward_process() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("pgrep -f 'kubectl port-forward .*svc/ml-pipeline-ui 8080:80'", "4242")
+            .into_runner();
+
+        let forward = PortForward { runner, ..Default::default() }
+            .with_kfp_only(true)
+            .with_port("8080");
+
+        assert!(forward.is_present().await.unwrap());
+        assert!(!forward.with_port("9090").is_present().await.unwrap());
+        assert_eq!(recorder.commands(), vec![
+            "pgrep -f 'kubectl port-forward .*svc/ml-pipeline-ui 8080:80'",
+            "pgrep -f 'kubectl port-forward .*svc/ml-pipeline-ui 9090:80'"
+        ]);
+    }
+}
@@ -0,0 +1,40 @@
+use std::{fmt, str::FromStr};
+use anyhow::{Error, anyhow};
+use serde::Deserialize;
+
+pub static PROVIDERS: [&str; 3] = ["k3d", "kind", "minikube"];
+
+/// The tool that creates (and deletes) the local kubernetes cluster.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    K3d,
+    Kind,
+    Minikube,
+}
+
+impl FromStr for Provider {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "k3d" => Ok(Provider::K3d),
+            "kind" => Ok(Provider::Kind),
+            "minikube" => Ok(Provider::Minikube),
+            other => Err(anyhow!("`{}` is not a cluster provider (use one of {}).", other, PROVIDERS.join(", ")))
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Provider::K3d => "k3d",
+            Provider::Kind => "kind",
+            Provider::Minikube => "minikube",
+        };
+
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::{
+    services::model::{Nameable, Ensurable, Removable, Inspectable, Trackable, verify_present},
+    services::command::{Cmd, Runner}
+};
+
+static NAME: &str = "Registry";
+
+/// A local container registry (managed by k3d), which the cluster pulls the images pushed to it from.
+#[derive(Default)]
+pub struct Registry {
+    registry_name: String,
+    registry_port: u16,
+    runner: Runner
+}
+
+impl Registry {
+    pub fn with_registry_name(mut self, n: &str) -> Self {
+        self.registry_name = n.to_owned();
+        self
+    }
+
+    pub fn with_registry_port(mut self, p: u16) -> Self {
+        self.registry_port = p;
+        self
+    }
+
+    /// The name of the registry container (k3d prefixes it).
+    fn container_name(&self) -> String {
+        format!("k3d-{}", self.registry_name)
+    }
+
+    /// The address that the cluster pulls from (e.g., `k3d-kfpl-registry:5000/my-component`).
+    pub fn cluster_address(&self) -> String {
+        format!("{}:{}", self.container_name(), self.registry_port)
+    }
+
+    /// The address that the host pushes to (e.g., `localhost:5000/my-component`).
+    pub fn push_address(&self) -> String {
+        format!("localhost:{}", self.registry_port)
+    }
+}
+
+impl Nameable for Registry {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+#[async_trait]
+impl Ensurable for Registry {
+    async fn is_present(&self) -> Result<bool> {
+        let ps_out = Cmd::new("docker")
+            .arg("ps")
+            .arg("--filter")
+            .arg(format!("name={}", self.container_name()))
+            .output(&self.runner).await?.stdout;
+
+        Ok(ps_out.contains(&self.container_name()))
+    }
+
+    async fn make_present(&self) -> Result<()> {
+        Cmd::new("k3d")
+            .arg("registry")
+            .arg("create")
+            .arg(&self.registry_name)
+            .args(["--port", &self.registry_port.to_string()])
+            .context("Unable to create the local registry.")
+            .run(&self.runner).await?;
+
+        verify_present(self, "Unable to verify that the local registry is running.").await
+    }
+}
+
+#[async_trait]
+impl Removable for Registry {
+    async fn make_not_present(&self) -> Result<()> {
+        Cmd::new("k3d")
+            .arg("registry")
+            .arg("delete")
+            .arg(self.container_name())
+            .context("Unable to delete the local registry.")
+            .run(&self.runner).await
+    }
+}
+
+impl Inspectable for Registry {}
+
+impl Trackable for Registry {
+    fn state_key(&self) -> String {
+        format!("{} ({})", NAME, self.registry_name)
+    }
+
+    fn install_options(&self) -> BTreeMap<String, String> {
+        let mut options = BTreeMap::new();
+        options.insert("registry_name".to_owned(), self.registry_name.clone());
+        options.insert("registry_port".to_owned(), self.registry_port.to_string());
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    fn registry(runner: Runner) -> Registry {
+        Registry { runner, ..Default::default() }
+            .with_registry_name("kfpl-registry")
+            .with_registry_port(5000)
+    }
+
+    #[tokio::test]
+    async fn make_present_creates_the_registry() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("docker ps", "abc123 registry:2 k3d-kfpl-registry")
+            .into_runner();
+
+        registry(runner).make_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            "k3d registry create kfpl-registry --port 5000",
+            "docker ps --filter name=k3d-kfpl-registry",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn make_not_present_deletes_the_registry_container() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        registry(runner).make_not_present().await.unwrap();
+
+        assert_eq!(recorder.commands(), vec!["k3d registry delete k3d-kfpl-registry"]);
+    }
+
+    #[test]
+    fn addresses_follow_the_name_and_port() {
+        let registry = Registry::default().with_registry_name("kfpl-registry").with_registry_port(5000);
+
+        assert_eq!(registry.cluster_address(), "k3d-kfpl-registry:5000");
+        assert_eq!(registry.push_address(), "localhost:5000");
+    }
+}
@@ -0,0 +1,267 @@
+use std::{fmt, io::{self, Write}, time::Instant};
+use tokio::time::Duration;
+use anyhow::{Result, Context, anyhow};
+use yansi::Paint;
+
+use crate::services::command::{Cmd, Runner, is_dry_run};
+
+static TAB: &str = "  ";
+static DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+static INITIAL_DELAY: Duration = Duration::from_secs(1);
+static MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// A resource (or every resource of a kind in a namespace) that has to exist, and reach a condition.
+#[derive(Clone, Debug, PartialEq)]
+struct Resource {
+    kind: &'static str,
+    name: Option<String>,
+    namespace: Option<String>,
+    condition: &'static str,
+}
+
+impl Resource {
+    /// Returns the resources that are still pending (with why), as of one query.
+    async fn pending(&self, runner: &Runner) -> Result<Vec<String>> {
+        let condition = format!("{{.status.conditions[?(@.type==\"{}\")].status}}", self.condition);
+        let lowercase = self.condition.to_lowercase();
+
+        let mut get = Cmd::new("kubectl").arg("get");
+
+        get = match &self.name {
+            Some(name) => get.arg(format!("{}/{}", self.kind, name)),
+            None => get.arg(self.kind)
+        };
+
+        if let Some(namespace) = &self.namespace {
+            get = get.args(["-n", namespace]);
+        }
+
+        match &self.name {
+            Some(name) => {
+                let output = get.arg("-o").arg(format!("jsonpath={}", condition)).output(runner).await?;
+
+                if !output.success {
+                    Ok(vec![format!("{}/{} (not created)", self.kind, name)])
+                } else if output.stdout.trim() != "True" {
+                    Ok(vec![format!("{}/{} (not {})", self.kind, name, lowercase)])
+                } else {
+                    Ok(Vec::new())
+                }
+            },
+            None => {
+                let output = get.arg("-o").arg(format!("jsonpath={{range .items[*]}}{{.metadata.name}}={}{{\"\\n\"}}{{end}}", condition)).output(runner).await?;
+                let items: Vec<(&str, &str)> = output.stdout.lines().filter_map(|l| l.trim().split_once('=')).collect();
+
+                if !output.success || items.is_empty() {
+                    return Ok(vec![format!("{} in {} (none created)", self.kind, self.namespace.as_deref().unwrap_or("default"))]);
+                }
+
+                Ok(items.into_iter()
+                    .filter(|(_, status)| *status != "True")
+                    .map(|(name, _)| format!("{}/{} (not {})", self.kind, name, lowercase))
+                    .collect())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}/{}", self.kind, name)?,
+            None => write!(f, "every {}", self.kind)?
+        }
+
+        if let Some(namespace) = &self.namespace {
+            write!(f, " -n {}", namespace)?;
+        }
+
+        write!(f, " ({})", self.condition.to_lowercase())
+    }
+}
+
+/// A description of the resources that a deployment step waits on, which are polled (with backoff) until each one
+/// exists and reaches its condition, or the timeout runs out (rather than sleeping, and then calling `kubectl wait`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rollout {
+    resources: Vec<Resource>,
+    timeout: Duration,
+    context: String,
+}
+
+impl Rollout {
+    pub fn new() -> Self {
+        Self {
+            resources: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            context: "Unable to wait for the rollout.".to_owned(),
+        }
+    }
+
+    /// Waits for a deployment to be available.
+    pub fn deployment(self, namespace: &str, name: &str) -> Self {
+        self.resource("deploy", Some(name), Some(namespace), "Available")
+    }
+
+    /// Waits for every deployment in the namespace (at least one) to be available.
+    pub fn deployments(self, namespace: &str) -> Self {
+        self.resource("deploy", None, Some(namespace), "Available")
+    }
+
+    /// Waits for a job to complete.
+    pub fn job(self, namespace: &str, name: &str) -> Self {
+        self.resource("job", Some(name), Some(namespace), "Complete")
+    }
+
+    /// Waits for a custom resource definition to be established.
+    pub fn crd(self, name: &str) -> Self {
+        self.resource("crd", Some(name), None, "Established")
+    }
+
+    fn resource(mut self, kind: &'static str, name: Option<&str>, namespace: Option<&str>, condition: &'static str) -> Self {
+        self.resources.push(Resource { kind, name: name.map(str::to_owned), namespace: namespace.map(str::to_owned), condition });
+        self
+    }
+
+    /// Sets how long to wait overall (if any, rather than the default of 10 minutes).
+    pub fn timeout(mut self, t: Option<Duration>) -> Self {
+        self.timeout = t.unwrap_or(DEFAULT_TIMEOUT);
+        self
+    }
+
+    /// Sets the error message reported when the resources are not ready in time.
+    pub fn context(mut self, c: &str) -> Self {
+        self.context = c.to_owned();
+        self
+    }
+
+    /// Polls until every resource is ready, while showing which ones are still pending.
+    pub async fn run(self, runner: &Runner) -> Result<()> {
+        // The resources of a planned deployment never show up.
+        if is_dry_run() {
+            println!("{}{}{} {}", TAB, TAB, Paint::yellow("would wait for:"), self);
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        let mut waited = Duration::from_secs(0);
+        let mut delay = INITIAL_DELAY;
+
+        loop {
+            let mut pending = Vec::new();
+            for resource in &self.resources {
+                pending.extend(resource.pending(runner).await?);
+            }
+
+            // The waits are counted as well, since a test runner does not actually pause.
+            let elapsed = start.elapsed().max(waited);
+
+            if pending.is_empty() {
+                println!("\r{}{}All {} ready after {}s.\x1b[K", TAB, TAB, self.resources.len(), elapsed.as_secs());
+                return Ok(());
+            }
+
+            if elapsed >= self.timeout {
+                println!();
+                return Err(anyhow!("Still pending after {}s: {}.", elapsed.as_secs(), pending.join(", "))).context(self.context);
+            }
+
+            // Overwrites the previous progress line.
+            print!("\r{}{}{} {} ({}s) ...\x1b[K", TAB, TAB, Paint::yellow("pending:"), pending.join(", "), elapsed.as_secs());
+            io::stdout().flush()?;
+
+            runner.wait(delay).await;
+            waited += delay;
+            delay = (delay * 2).min(MAX_DELAY);
+        }
+    }
+}
+
+impl Default for Rollout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let resources: Vec<String> = self.resources.iter().map(ToString::to_string).collect();
+
+        write!(f, "{} (within {}s)", resources.join(", "), self.timeout.as_secs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::command::fake::RecordingRunner;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn run_returns_once_every_resource_is_ready() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("kubectl get job/helm-install-traefik", "True")
+            .respond("kubectl get deploy/traefik", "True")
+            .into_runner();
+
+        Rollout::new()
+            .job("kube-system", "helm-install-traefik")
+            .deployment("kube-system", "traefik")
+            .run(&runner).await.unwrap();
+
+        assert_eq!(recorder.commands(), vec![
+            r#"kubectl get job/helm-install-traefik -n kube-system -o 'jsonpath={.status.conditions[?(@.type=="Complete")].status}'"#,
+            r#"kubectl get deploy/traefik -n kube-system -o 'jsonpath={.status.conditions[?(@.type=="Available")].status}'"#,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn run_reads_every_deployment_in_the_namespace() {
+        let (runner, recorder) = RecordingRunner::default()
+            .respond("kubectl get deploy -n kubeflow", "minio=True\nml-pipeline=True\n")
+            .into_runner();
+
+        Rollout::new().deployments("kubeflow").run(&runner).await.unwrap();
+
+        assert_eq!(recorder.commands().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn run_backs_off_until_the_timeout() {
+        let (runner, recorder) = RecordingRunner::default().into_runner();
+
+        let err = Rollout::new()
+            .crd("applications.app.k8s.io")
+            .timeout(Some(Duration::from_secs(5)))
+            .context("Unable to wait for the KFP CRDs.")
+            .run(&runner).await.unwrap_err();
+
+        // Polls at 0s, 1s, 3s, and 7s (which is past the timeout).
+        assert_eq!(recorder.commands().len(), 4);
+        assert_eq!(err.to_string(), "Unable to wait for the KFP CRDs.");
+        assert_eq!(format!("{:#}", err), "Unable to wait for the KFP CRDs.: Still pending after 7s: crd/applications.app.k8s.io (not created).");
+    }
+
+    #[tokio::test]
+    async fn run_reports_the_deployments_that_are_not_available() {
+        let (runner, _) = RecordingRunner::default()
+            .respond("kubectl get deploy -n kubeflow", "minio=True\nmysql=False\nml-pipeline=\n")
+            .into_runner();
+
+        let err = Rollout::new()
+            .deployments("kubeflow")
+            .timeout(Some(Duration::from_secs(0)))
+            .run(&runner).await.unwrap_err();
+
+        assert_eq!(format!("{:#}", err), "Unable to wait for the rollout.: Still pending after 0s: deploy/mysql (not available), deploy/ml-pipeline (not available).");
+    }
+
+    #[test]
+    fn display_lists_the_resources() {
+        let rollout = Rollout::new()
+            .crd("applications.app.k8s.io")
+            .deployments("kubeflow");
+
+        assert_eq!(rollout.to_string(), "crd/applications.app.k8s.io (established), every deploy -n kubeflow (available) (within 600s)");
+    }
+}
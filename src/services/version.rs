@@ -0,0 +1,84 @@
+use std::{fmt, cmp::Ordering};
+
+/// A `major.minor.patch` version, which ignores any `v` prefix, and any pre-release or build suffix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.strip_prefix('v').unwrap_or(text);
+        let core = text.split(|c: char| !(c.is_ascii_digit() || c == '.')).next().unwrap_or_default();
+
+        let mut parts = core.split('.').filter(|p| !p.is_empty()).map(str::parse::<u64>);
+
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+
+        Some(Self { major, minor, patch })
+    }
+
+    /// Whether `self` is at least `minimum`.
+    pub fn satisfies(&self, minimum: &Version) -> bool {
+        self.cmp(minimum) != Ordering::Less
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Finds the first version-looking token (e.g., `v1.19.2`, or `20.0.2`) in some command output.
+pub(crate) fn parse_version(text: &str) -> Option<String> {
+    text
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+'))
+        .map(|token| token.trim_end_matches('.'))
+        .find(|token| {
+            let digits = token.strip_prefix('v').unwrap_or(token);
+            let mut parts = digits.split('.');
+
+            let major = parts.next().unwrap_or_default();
+            let minor = parts.next().unwrap_or_default();
+
+            !major.is_empty() && major.chars().all(|c| c.is_ascii_digit()) && minor.starts_with(|c: char| c.is_ascii_digit())
+        })
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_finds_the_first_version_token() {
+        assert_eq!(parse_version("k3d version v4.2.0\nk3s version v1.20.2-k3s1 (default)").as_deref(), Some("v4.2.0"));
+        assert_eq!(parse_version("Docker version 19.03.8, build afacb8b").as_deref(), Some("19.03.8"));
+        assert_eq!(parse_version("pip 20.0.2 from /usr/lib/python3/dist-packages/pip (python 3.8)").as_deref(), Some("20.0.2"));
+        assert_eq!(parse_version("no version here"), None);
+    }
+
+    #[test]
+    fn versions_ignore_prefixes_and_suffixes() {
+        assert_eq!(Version::parse("v1.1.0-0-g9a3621e"), Some(Version { major: 1, minor: 1, patch: 0 }));
+        assert_eq!(Version::parse("19.03.8"), Some(Version { major: 19, minor: 3, patch: 8 }));
+        assert_eq!(Version::parse("v1.20.2+k3s1"), Some(Version { major: 1, minor: 20, patch: 2 }));
+        assert_eq!(Version::parse("1.4"), Some(Version { major: 1, minor: 4, patch: 0 }));
+        assert_eq!(Version::parse("latest"), None);
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        let minimum = Version::parse("v4.0.0").unwrap();
+
+        assert!(Version::parse("v4.2.0").unwrap().satisfies(&minimum));
+        assert!(Version::parse("v10.0.0").unwrap().satisfies(&minimum));
+        assert!(!Version::parse("v3.4.0").unwrap().satisfies(&minimum));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{env, fs, path::PathBuf, collections::BTreeMap, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+
+static STATE_FILE: &str = "kfpl/state.toml";
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// What kfpl itself has made present on this machine (persisted between runs).
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub installs: BTreeMap<String, Install>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Install {
+    /// The version that kfpl installed (or deployed), if the entity has one.
+    pub version: Option<String>,
+    /// When the install happened (seconds since the Unix epoch).
+    pub installed_at: u64,
+    /// The files written by the install.
+    pub paths: Vec<String>,
+    /// The options that the entity was made present with (e.g., the cluster name, or the image).
+    pub options: BTreeMap<String, String>,
+}
+
+impl Install {
+    /// Describes how this install differs from `other` (e.g., `1.0.4, k3d_image = rancher/k3s:v1.20.2-k3s1`), or
+    /// returns `None` when they match (the paths, and the timestamp, are ignored).
+    pub fn difference(&self, other: &Install) -> Option<String> {
+        let mut differences = Vec::new();
+
+        if self.version != other.version {
+            differences.push(self.version.clone().unwrap_or_else(|| "an unknown version".to_owned()));
+        }
+
+        for (key, value) in &self.options {
+            if other.options.get(key) != Some(value) {
+                differences.push(format!("{} = {}", key, value));
+            }
+        }
+
+        if differences.is_empty() { None } else { Some(differences.join(", ")) }
+    }
+}
+
+/// The current time, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+impl State {
+    /// Returns `$XDG_STATE_HOME/kfpl/state.toml` (or `~/.local/state/kfpl/state.toml`).
+    #[cfg(not(test))]
+    pub fn path() -> Result<PathBuf> {
+        let state_dir = env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+            .context("Unable to locate the state directory (neither `XDG_STATE_HOME` nor `HOME` is set).")?;
+
+        Ok(state_dir.join(STATE_FILE))
+    }
+
+    /// Keeps the tests away from the real state file.
+    #[cfg(test)]
+    pub fn path() -> Result<PathBuf> {
+        Ok(env::temp_dir().join(format!("kfpl-test-{}", std::process::id())).join(STATE_FILE))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let text = fs::read_to_string(&path).with_context(|| format!("Unable to read the state file `{}`.", path.display()))?;
+
+        toml::from_str(&text).with_context(|| format!("Unable to parse the state file `{}`.", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        let text = toml::to_string(self).context("Unable to serialize the state.")?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Unable to create the state directory `{}`.", dir.display()))?;
+        }
+
+        // Write, and then rename, so that an interrupted run never leaves a truncated state file.
+        let temp_path = path.with_extension("toml.tmp");
+        fs::write(&temp_path, text).with_context(|| format!("Unable to write the state file `{}`.", temp_path.display()))?;
+        fs::rename(&temp_path, &path).with_context(|| format!("Unable to replace the state file `{}`.", path.display()))
+    }
+
+    /// Loads the state, applies `f`, and saves the result.
+    pub fn update<F>(f: F) -> Result<()>
+        where F: FnOnce(&mut State)
+    {
+        let _guard = UPDATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut state = Self::load()?;
+        f(&mut state);
+        state.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(version: &str, image: &str) -> Install {
+        let mut install = Install { version: Some(version.to_owned()), ..Default::default() };
+        install.options.insert("k3d_image".to_owned(), image.to_owned());
+        install
+    }
+
+    #[test]
+    fn difference_ignores_the_timestamp_and_paths() {
+        let recorded = Install { installed_at: 1, paths: vec!["/usr/local/bin/k3d".to_owned()], ..install("1.0.4", "rancher/k3s") };
+
+        assert_eq!(recorded.difference(&install("1.0.4", "rancher/k3s")), None);
+    }
+
+    #[test]
+    fn difference_lists_the_version_and_options_that_changed() {
+        let recorded = install("1.0.4", "rancher/k3s:v1.20.2-k3s1");
+
+        assert_eq!(recorded.difference(&install("1.0.4", "rancher/k3s:v1.21.1-k3s1")).as_deref(), Some("k3d_image = rancher/k3s:v1.20.2-k3s1"));
+        assert_eq!(recorded.difference(&install("1.4.0", "rancher/k3s:v1.20.2-k3s1")).as_deref(), Some("1.0.4"));
+    }
+
+    #[test]
+    fn state_round_trips_through_toml() {
+        let mut state = State::default();
+        state.installs.insert("KFP Service (kfp-local)".to_owned(), install("1.0.4", "rancher/k3s"));
+
+        let parsed: State = toml::from_str(&toml::to_string(&state).unwrap()).unwrap();
+
+        assert_eq!(parsed.installs, state.installs);
+    }
+}